@@ -0,0 +1,122 @@
+use std::f32::consts::TAU;
+
+/// Timer frames per second, which is also how long one frame of beep lasts.
+const FRAMES_PER_SECOND: u32 = 60;
+/// Bits in an XO-CHIP audio pattern, played back most significant bit of the first byte first.
+const PATTERN_BITS: usize = 128;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Waveform {
+    #[default]
+    Square,
+    Sine,
+}
+
+/// Generates `frames` 60 Hz frames worth of a `freq` Hz tone in the range -1.0..=1.0,
+/// ready to be fed to an audio backend running at `sample_rate`.
+pub fn generate_beep_samples(
+    sample_rate: u32,
+    frames: u32,
+    freq: f32,
+    waveform: Waveform,
+) -> Vec<f32> {
+    let count = (sample_rate as u64 * frames as u64 / FRAMES_PER_SECOND as u64) as usize;
+    (0..count)
+        .map(|n| {
+            let phase = (n as f32 * freq / sample_rate as f32).fract();
+            match waveform {
+                Waveform::Square if phase < 0.5 => 1.0,
+                Waveform::Square => -1.0,
+                Waveform::Sine => (phase * TAU).sin(),
+            }
+        })
+        .collect()
+}
+
+/// Bits per second an XO-CHIP audio pattern plays at for a given pitch register value.
+/// The default pitch of 64 plays at 4000 Hz, and every 48 steps double or halve that.
+pub fn pattern_playback_rate(pitch: u8) -> f32 {
+    4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0)
+}
+
+/// Generates `frames` 60 Hz frames of an XO-CHIP audio pattern looping at `playback_rate`
+/// bits per second, set bits becoming 1.0 and clear ones -1.0.
+pub fn generate_pattern_samples(
+    sample_rate: u32,
+    frames: u32,
+    pattern: &[u8; 16],
+    playback_rate: f32,
+) -> Vec<f32> {
+    let count = (sample_rate as u64 * frames as u64 / FRAMES_PER_SECOND as u64) as usize;
+    (0..count)
+        .map(|n| {
+            let bit = (n as f32 * playback_rate / sample_rate as f32) as usize % PATTERN_BITS;
+            if pattern[bit / 8] & (0x80 >> (bit % 8)) != 0 {
+                1.0
+            } else {
+                -1.0
+            }
+        })
+        .collect()
+}
+
+/// An audio output that can be switched on and off, e.g. a looping tone on a sound device.
+/// The interpreter only calls `set_playing` when the sound timer starts or stops.
+pub trait Beeper: Send {
+    fn set_playing(&mut self, playing: bool);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_count() {
+        assert_eq!(
+            generate_beep_samples(44100, 30, 440.0, Waveform::Square).len(),
+            44100 * 30 / 60
+        );
+        assert_eq!(
+            generate_beep_samples(48000, 1, 440.0, Waveform::Sine).len(),
+            800
+        );
+        assert!(generate_beep_samples(48000, 0, 440.0, Waveform::Square).is_empty());
+    }
+
+    #[test]
+    fn square_wave_alternates_sign() {
+        // 4 samples per period: two high, two low
+        let samples = generate_beep_samples(240, 1, 60.0, Waveform::Square);
+        assert_eq!(samples, [1.0, 1.0, -1.0, -1.0]);
+    }
+
+    #[test]
+    fn pattern_playback_rate_doubles_every_48_steps() {
+        assert_eq!(pattern_playback_rate(64), 4000.0);
+        assert!((pattern_playback_rate(112) - 8000.0).abs() < 0.01);
+        assert!((pattern_playback_rate(16) - 2000.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn pattern_samples_follow_bits() {
+        // One sample per bit: 1010 0000, then silence
+        let mut pattern = [0; 16];
+        pattern[0] = 0b1010_0000;
+        let samples = generate_pattern_samples(240, 1, &pattern, 240.0);
+        assert_eq!(samples, [1.0, -1.0, 1.0, -1.0]);
+
+        let samples = generate_pattern_samples(600, 1, &pattern, 300.0);
+        assert_eq!(
+            samples,
+            [1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0, -1.0, -1.0, -1.0]
+        );
+    }
+
+    #[test]
+    fn sine_wave() {
+        let samples = generate_beep_samples(240, 1, 60.0, Waveform::Sine);
+        assert!(samples[0].abs() < 1e-6);
+        assert!((samples[1] - 1.0).abs() < 1e-6);
+        assert!((samples[3] + 1.0).abs() < 1e-6);
+    }
+}
@@ -1,14 +1,45 @@
-use std::time::Instant;
+use crate::audio::{self, Beeper, Waveform};
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    error::Error,
+    fmt,
+    io::{self, Read, Write},
+    mem,
+    ops::Range,
+    sync::{
+        mpsc::{self, Receiver, Sender, SyncSender, TryRecvError, TrySendError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 const INSTRUCTIONS_PER_SECOND: u32 = 700;
+/// The COSMAC VIP's 1.76 MHz CPU takes 8 clock cycles per machine cycle.
+const MACHINE_CYCLES_PER_SECOND: u32 = 1_760_000 / 8;
 const SCREEN_WIDTH: usize = 64;
 const SCREEN_HEIGHT: usize = 32;
+const HIRES_WIDTH: usize = 128;
+const HIRES_HEIGHT: usize = 64;
+/// XO-CHIP bit planes. Each screen buffer entry holds one bit per plane.
+const PLANE_COUNT: usize = 2;
+/// A screen buffer entry with no plane lit, what front-ends should clear their buffers to.
+const PIXEL_OFF: u8 = 0;
+
+/// XO-CHIP pitch register value that plays audio patterns at 4000 bits per second.
+const DEFAULT_PITCH: u8 = 64;
 
 const STACK_SIZE: usize = 48;
 const MEMORY_SIZE: usize = 4096;
-const TIMER_DECREMENT_FREQUENCY: f32 = 60.0;
+const TIMER_DECREMENT_FREQUENCY: u32 = 60;
 const PC_START_ADDRESS: usize = 0x200;
 const FONT_START_ADDRESS: usize = 0x50;
+const BUNDLE_HEADER: &str = "chip8-bundle";
+const TEST_ROM_SEED: u64 = 0xC8;
+/// Screen snapshots a spawned interpreter buffers before dropping frames.
+const FRAME_QUEUE_SIZE: usize = 2;
+/// `i` is a 16-bit register, arithmetic on it wraps at this mask.
+const I_MASK: usize = 0xFFFF;
 const FONT: [u8; 16 * 5] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
@@ -28,243 +59,6139 @@ const FONT: [u8; 16 * 5] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
-struct Interpreter {
-    pc: usize,
-    i: usize,
-    stack: Stack,
-    memory: [u8; MEMORY_SIZE],
-    registers: [u16; 16],
-    timers: Timers,
-    screen_buffer: [u8; SCREEN_WIDTH * SCREEN_HEIGHT],
+/// What `step` does when it fetches an opcode it can't decode.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum UnimplementedPolicy {
+    /// Stop and report `Fault::DecodeError`, leaving `pc` on the offending opcode.
+    #[default]
+    Fault,
+    /// Treat the opcode as a no-op and continue with the next one.
+    Skip,
+    /// Panic with the opcode and its address, for catching gaps in the decoder while testing.
+    Panic,
 }
 
-impl Interpreter {
-    pub fn new() -> Self {
-        let mut memory = [0; MEMORY_SIZE];
-        for i in 0..FONT.len() {
-            memory[FONT_START_ADDRESS as usize + i] = FONT[i];
+/// How `tick_frame` decides how many instructions fit into a frame.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum Timing {
+    /// Every instruction takes the same time, `clock_hz` of them per second.
+    #[default]
+    Flat,
+    /// Instructions take their `Instruction::cycle_cost` out of a COSMAC VIP frame's worth of
+    /// machine cycles, so cheap instructions run faster than slow ones like draws.
+    CycleCost,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StatsFormat {
+    /// An `opcode,count` header followed by one line per high nibble.
+    Csv,
+    /// An object from high nibble to count, e.g. `{"0":0,"1":3,...}`.
+    Json,
+}
+
+/// How `Timers` keeps track of partial frames between updates.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum TimerAccumulator {
+    /// An `f32` fraction of a frame, which slowly drifts over long sessions.
+    #[default]
+    Float,
+    /// Whole nanoseconds, so the timers tick at exactly `timer_hz` no matter how long they run.
+    Nanos,
+}
+
+/// How `frame_buffer_rgba` lays out the screen, for displays mounted upside down or mirrored.
+/// Only the rendered output changes, the screen buffer and collisions never do.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum DisplayTransform {
+    #[default]
+    Normal,
+    /// Mirrored left to right.
+    FlipX,
+    /// Mirrored top to bottom.
+    FlipY,
+    Rotate180,
+}
+
+/// Behaviours that differ between CHIP-8 implementations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Quirks {
+    /// `FX55`/`FX65` leave `i` pointing past the last register they touched.
+    memory_increments_i: bool,
+    /// Sprites are cut off at the left and right edges instead of wrapping around.
+    clip_x: bool,
+    /// Sprites are cut off at the top and bottom edges instead of wrapping around.
+    ///
+    /// No real platform mixes the two: the COSMAC VIP and SUPER-CHIP clip both axes and XO-CHIP
+    /// wraps both. The axes are still separate for ROMs tuned against emulators that only wrapped
+    /// one, like games that scroll sprites off the sides but expect the bottom edge to clip.
+    clip_y: bool,
+    /// The display stays at 128x64 and lo-res mode draws every pixel as a 2x2 block.
+    lores_scaling: bool,
+    /// Pixels scrolled off one edge come back in on the other, like on XO-CHIP. Otherwise the
+    /// vacated rows or columns are cleared, like on SUPER-CHIP.
+    scroll_wraps: bool,
+    /// `8XY6`/`8XYE` shift `VY` into `VX`, like on the COSMAC VIP. Otherwise `VX` is shifted in
+    /// place and `VY` is ignored, like on SUPER-CHIP.
+    shift_uses_vy: bool,
+    /// `BNNN` jumps to `NNN` plus `VX`, `X` being the top nibble of `NNN`, like on SUPER-CHIP.
+    /// Otherwise it adds `V0`, like on the COSMAC VIP.
+    jump_uses_vx: bool,
+}
+
+impl Quirks {
+    pub fn cosmac_vip() -> Self {
+        Self {
+            memory_increments_i: true,
+            clip_x: true,
+            clip_y: true,
+            lores_scaling: false,
+            scroll_wraps: false,
+            shift_uses_vy: true,
+            jump_uses_vx: false,
         }
+    }
 
+    pub fn super_chip() -> Self {
         Self {
-            pc: PC_START_ADDRESS,
-            i: 0,
-            stack: Stack::new(),
-            memory,
-            registers: [0; 16],
-            timers: Timers::new(),
-            screen_buffer: [0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            memory_increments_i: false,
+            clip_x: true,
+            clip_y: true,
+            lores_scaling: true,
+            scroll_wraps: false,
+            shift_uses_vy: false,
+            jump_uses_vx: true,
         }
     }
 
-    pub fn load_program(&mut self, bytes: &[u8]) {
-        for (i, byte) in bytes.iter().enumerate() {
-            self.memory[PC_START_ADDRESS + i] = *byte;
+    pub fn xo_chip() -> Self {
+        Self {
+            memory_increments_i: true,
+            clip_x: false,
+            clip_y: false,
+            lores_scaling: false,
+            scroll_wraps: true,
+            shift_uses_vy: true,
+            jump_uses_vx: false,
         }
     }
+}
 
-    fn fetch_instruction(&mut self) -> u16 {
-        let instruction = ((self.memory[self.pc] as u16) << 8) | self.memory[self.pc + 1] as u16;
-        self.pc += 2;
-        instruction
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::cosmac_vip()
     }
 }
 
-#[derive(Debug, PartialEq)]
-enum Instruction {
-    NotImplemented,
-    ClearScreen,
-    Jump(usize),
-    SetRegister(usize, u16),
-    AddToRegister(usize, u16),
-    SetI(u16),
-    DrawSprite(usize, usize, u8),
+#[derive(Debug, Clone)]
+struct InterpreterConfig {
+    unimplemented_policy: UnimplementedPolicy,
+    /// Instructions executed per second of emulated time, must not be 0.
+    clock_hz: u32,
+    quirks: Quirks,
+    timing: Timing,
+    timer_accumulator: TimerAccumulator,
+    /// Timer decrements per second, must not be 0. Frames follow the timers, so this also sets
+    /// how many instructions `tick_frame` runs and how often `run` repaints.
+    timer_hz: u32,
+    /// How much a pixel's fade value drops per frame after it turns off. `None` disables fading.
+    fade_decay: Option<u8>,
+    display_transform: DisplayTransform,
+    /// Accesses through `i` wrap around at the end of memory like on the original hardware,
+    /// instead of faulting with `Fault::MemoryOutOfBounds`. Instruction fetches follow the same
+    /// rule, with `pc` wrapping from 0xFFE to 0x000 rather than faulting with `PcOutOfBounds`.
+    i_wraps: bool,
+    /// Fault on writes below `PC_START_ADDRESS`, where the font and interpreter live.
+    protect_reserved: bool,
+    /// Fault on jumps and calls below `PC_START_ADDRESS`, which usually come from a corrupted
+    /// jump table.
+    protect_reserved_jumps: bool,
+    /// Fault when `pc` is odd at fetch time, which usually means a jump went wrong.
+    require_aligned_pc: bool,
+    /// Report `Diagnostic::DrawThenClear` when a clear follows a draw within this many cycles
+    /// in the same frame. `None` disables the check.
+    flicker_window: Option<u64>,
+    /// Report `Diagnostic::StackImbalance` when calls outnumber returns, or the other way
+    /// around, by more than `threshold` within a `(window, threshold)` cycle window. `None`
+    /// disables the check.
+    stack_imbalance: Option<(u64, usize)>,
+    /// Report `Diagnostic::UnsetI` when an instruction accesses memory through `i` before the
+    /// program ever set it and it still points below `PC_START_ADDRESS`.
+    check_unset_i: bool,
+    /// Drop `KeyEvent::Down` for keys that are already held, i.e. OS key repeat.
+    ignore_key_repeat: bool,
+    /// Apply queued key events once at the start of each frame and let `EX9E`/`EXA1` see every
+    /// key that was down at any point in between, so a tap shorter than a frame isn't missed.
+    latch_keys: bool,
+    /// Where the hex font is loaded. `FX29` points `i` into the font at this address.
+    font_address: usize,
+    /// Seed for `CXNN`. `None` seeds from the system time.
+    rng_seed: Option<u64>,
+    /// Maximum subroutine nesting. The original interpreter allowed 16, up to `STACK_SIZE` is accepted.
+    stack_depth: usize,
+    /// Byte that memory and the registers start out as, e.g. `0xAA` to make ROMs that rely on
+    /// zeroed memory fail loudly. The font is still loaded on top.
+    fill_pattern: u8,
 }
 
-impl Instruction {
-    fn from_raw(bytes: u16) -> Self {
-        match Self::nibble_left(bytes, 0) {
-            0 => match bytes {
-                0x00E0 => Self::ClearScreen,
-                _ => Self::NotImplemented,
-            },
-            1 => Self::Jump((bytes & 0x0FFF) as usize),
-            6 => Self::SetRegister(Self::nibble_left(bytes, 1) as usize, bytes & 0x00FF),
-            7 => Self::AddToRegister(Self::nibble_left(bytes, 1) as usize, bytes & 0x00FF),
-            0xA => Self::SetI(bytes & 0x0FFF),
-            0xD => Self::DrawSprite(
-                Self::nibble_left(bytes, 1) as usize,
-                Self::nibble_left(bytes, 2) as usize,
-                Self::nibble_left(bytes, 3),
-            ),
-            _ => Self::NotImplemented,
+impl Default for InterpreterConfig {
+    fn default() -> Self {
+        Self {
+            unimplemented_policy: UnimplementedPolicy::default(),
+            clock_hz: INSTRUCTIONS_PER_SECOND,
+            quirks: Quirks::default(),
+            timing: Timing::default(),
+            timer_accumulator: TimerAccumulator::default(),
+            timer_hz: TIMER_DECREMENT_FREQUENCY,
+            fade_decay: None,
+            display_transform: DisplayTransform::default(),
+            i_wraps: false,
+            protect_reserved: false,
+            protect_reserved_jumps: false,
+            require_aligned_pc: false,
+            flicker_window: None,
+            stack_imbalance: None,
+            check_unset_i: false,
+            ignore_key_repeat: true,
+            latch_keys: false,
+            font_address: FONT_START_ADDRESS,
+            rng_seed: None,
+            stack_depth: 16,
+            fill_pattern: 0,
         }
     }
+}
 
-    fn nibble_left(bytes: u16, position: usize) -> u8 {
-        assert!(position < 4);
-        let mask = 0xF000 >> position * 4;
-        let shift = 12 - position * 4;
-        ((bytes & mask) >> shift) as u8
+#[derive(Debug, PartialEq)]
+enum BundleError {
+    MissingHeader,
+    MissingRom,
+    InvalidValue(String),
+}
+
+#[derive(Debug, PartialEq)]
+enum RomError {
+    Empty,
+    /// The ROM is this many bytes long and doesn't fit in memory above `PC_START_ADDRESS`.
+    TooLarge(usize),
+}
+
+#[derive(Debug, PartialEq)]
+enum MemError {
+    /// The bytes would run past the end of memory, starting at this address.
+    OutOfBounds(usize),
+}
+
+/// What `Interpreter::verify_rom` found out about a ROM.
+#[derive(Debug, Clone, PartialEq)]
+struct RomInfo {
+    size: usize,
+    /// The ROM uses SUPER-CHIP instructions like scrolling or hi-res mode.
+    super_chip: bool,
+    suggested_quirks: Quirks,
+}
+
+/// Source of time for the timers, so tests can drive them without sleeping. `Send` so the
+/// interpreter owning it can move to another thread.
+trait Clock: Send {
+    fn now(&self) -> Instant;
+}
+
+struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
     }
 }
 
-struct Stack {
-    data: [u8; STACK_SIZE],
-    position: usize,
+/// A clock that only moves when told to. Clones share the same time.
+#[derive(Clone)]
+struct MockClock {
+    start: Instant,
+    elapsed: Arc<Mutex<Duration>>,
 }
 
-impl Stack {
+impl MockClock {
     pub fn new() -> Self {
         Self {
-            data: [0; STACK_SIZE],
-            position: 0,
+            start: Instant::now(),
+            elapsed: Arc::new(Mutex::new(Duration::ZERO)),
         }
     }
 
-    pub fn push(&mut self, byte: u8) {
-        if self.position > STACK_SIZE - 1 {
-            panic!("stack overflow")
-        }
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+}
 
-        self.data[self.position] = byte;
-        self.position += 1;
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + *self.elapsed.lock().unwrap()
     }
+}
 
-    pub fn pop(&mut self) -> Option<u8> {
-        if self.position == 0 {
-            return None;
+/// Everything that can stop the interpreter while it runs a program.
+#[derive(Debug, Clone, PartialEq)]
+enum Fault {
+    PcOutOfBounds(usize),
+    /// `pc` was odd with `require_aligned_pc` set.
+    MisalignedPc(usize),
+    MemoryOutOfBounds(usize),
+    WriteToReserved(usize),
+    /// The jump or call at `pc` went to `target` with `protect_reserved_jumps` set.
+    JumpToReserved {
+        pc: usize,
+        target: usize,
+    },
+    DecodeError {
+        pc: usize,
+        opcode: u16,
+    },
+    /// The opcode decodes, but the interpreter can't execute it yet.
+    Unsupported {
+        pc: usize,
+        instruction: Instruction,
+    },
+    InvalidRegister(usize),
+    StackOverflow,
+    StackUnderflow,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PcOutOfBounds(pc) => write!(f, "program counter {pc:#05X} is outside of memory"),
+            Self::MisalignedPc(pc) => write!(f, "program counter {pc:#05X} is odd"),
+            Self::MemoryOutOfBounds(address) => {
+                write!(f, "memory access at {address:#05X} is outside of memory")
+            }
+            Self::WriteToReserved(address) => {
+                write!(f, "write to reserved memory at {address:#05X}")
+            }
+            Self::JumpToReserved { pc, target } => {
+                write!(
+                    f,
+                    "jump from {pc:#05X} into reserved memory at {target:#05X}"
+                )
+            }
+            Self::DecodeError { pc, opcode } => {
+                write!(f, "unknown opcode {opcode:#06X} at {pc:#05X}")
+            }
+            Self::Unsupported { pc, instruction } => {
+                write!(f, "unsupported instruction {instruction} at {pc:#05X}")
+            }
+            Self::InvalidRegister(x) => write!(f, "register V{x} doesn't exist"),
+            Self::StackOverflow => write!(f, "stack overflow"),
+            Self::StackUnderflow => write!(f, "return with an empty stack"),
         }
+    }
+}
 
-        self.position -= 1;
-        Some(self.data[self.position])
+impl Fault {
+    /// Address of the instruction that faulted, for the faults that know it.
+    pub fn pc(&self) -> Option<usize> {
+        match *self {
+            Self::PcOutOfBounds(pc)
+            | Self::MisalignedPc(pc)
+            | Self::JumpToReserved { pc, .. }
+            | Self::DecodeError { pc, .. }
+            | Self::Unsupported { pc, .. } => Some(pc),
+            _ => None,
+        }
     }
 }
 
-struct Timers {
-    pub delay_timer: u8,
-    pub sound_timer: u8,
-    last_update: Instant,
-    rounding_remainder: f32,
+impl Error for Fault {}
+
+/// Suspicious program behaviour noticed while running. Purely informational.
+#[derive(Debug, Clone, PartialEq)]
+enum Diagnostic {
+    /// A sprite was drawn and the screen cleared before the frame was shown, so the sprite
+    /// flickers. The ROM would likely benefit from the display wait quirk.
+    DrawThenClear { draw_pc: usize, clear_pc: usize },
+    /// Calls minus returns reached `delta` within one stack imbalance window, at the call or
+    /// return at `pc`. Usually a subroutine that jumps out instead of returning, or the reverse.
+    StackImbalance { pc: usize, delta: isize },
+    /// The instruction at `pc` accessed memory through `i` while it was still at its power-on
+    /// value, most likely a missing `ANNN`. `ANNN`, `FX1E` and `FX29` all count as setting it.
+    UnsetI { pc: usize },
 }
 
-impl Timers {
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KeyEvent {
+    Down(u8),
+    Up(u8),
+}
+
+/// Key state as seen by the instruction executed at `cycle`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RecordedInput {
+    cycle: u64,
+    keys: u16,
+    just_pressed: u16,
+}
+
+/// Log of the key state at every step where it changed or a key went down, no matter whether
+/// it came from `set_key` or queued events. Replaying it with `InputReplay` against a fresh
+/// interpreter with the same seed and clock reproduces the run exactly.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct InputRecorder {
+    inputs: Vec<RecordedInput>,
+    last_keys: u16,
+}
+
+impl InputRecorder {
     pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn inputs(&self) -> &[RecordedInput] {
+        &self.inputs
+    }
+
+    fn record(&mut self, cycle: u64, keys: u16, just_pressed: u16) {
+        if keys != self.last_keys || just_pressed != 0 {
+            self.inputs.push(RecordedInput {
+                cycle,
+                keys,
+                just_pressed,
+            });
+            self.last_keys = keys;
+        }
+    }
+}
+
+/// Feeds a recorded input log back into `step`, overriding live key input while it lasts.
+#[derive(Debug, Clone)]
+struct InputReplay {
+    inputs: VecDeque<RecordedInput>,
+}
+
+impl InputReplay {
+    pub fn new(recording: &InputRecorder) -> Self {
         Self {
-            delay_timer: 0,
-            sound_timer: 0,
-            last_update: Instant::now(),
-            rounding_remainder: 0.0,
+            inputs: recording.inputs.iter().copied().collect(),
         }
     }
 
-    pub fn decrement_timers(&mut self) {
-        let now = Instant::now();
-        let delta = now - self.last_update;
-        let amount = TIMER_DECREMENT_FREQUENCY * delta.as_secs_f32() + self.rounding_remainder;
-        self.rounding_remainder = amount - amount.floor();
-        let amount = amount.floor() as u8;
+    pub fn is_finished(&self) -> bool {
+        self.inputs.is_empty()
+    }
 
-        if self.delay_timer > amount {
-            self.delay_timer -= amount;
-        } else {
-            self.delay_timer = 0;
+    fn next_for(&mut self, cycle: u64) -> Option<RecordedInput> {
+        // Inputs from before `cycle` can't be honoured any more; skip over them.
+        while self.inputs.front()?.cycle < cycle {
+            self.inputs.pop_front();
         }
-        if self.sound_timer > amount {
-            self.sound_timer -= amount;
+        if self.inputs.front()?.cycle == cycle {
+            self.inputs.pop_front()
         } else {
-            self.sound_timer = 0;
-        }
-        if self.delay_timer + self.sound_timer == 0 {
-            self.rounding_remainder = 0.0;
+            None
         }
+    }
+}
 
-        self.last_update = now;
+/// What a single `Interpreter::step_result` did.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct StepResult {
+    instruction: Instruction,
+    /// The instruction drew, cleared or otherwise touched the screen, so it needs a redraw.
+    screen_changed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum RunOutcome {
+    /// Every requested cycle was executed.
+    Completed,
+    /// `pc` reached the requested address, or the subroutine being stepped over returned.
+    PcReached,
+    /// The program executed `00FD`.
+    Exited,
+    Fault(Fault),
+    /// A step changed a value watched with `add_watchpoint`.
+    WatchpointHit(WatchTarget),
+}
+
+/// A value a debugger wants to stop on, see `Interpreter::add_watchpoint`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WatchTarget {
+    Register(usize),
+    Memory(usize),
+}
+
+/// Handles to an interpreter running on its own thread, see `Interpreter::spawn`.
+struct InterpreterHandle {
+    keys: Sender<KeyEvent>,
+    frames: Receiver<Vec<u8>>,
+    thread: JoinHandle<RunOutcome>,
+}
+
+impl InterpreterHandle {
+    /// Queues a key event for the next frame. Returns `false` once the interpreter has stopped.
+    pub fn send_key(&self, event: KeyEvent) -> bool {
+        self.keys.send(event).is_ok()
+    }
+
+    /// Screen snapshots, one per frame. Frames are dropped while the receiver lags behind.
+    pub fn frames(&self) -> &Receiver<Vec<u8>> {
+        &self.frames
+    }
+
+    /// Stops the interpreter and returns why it stopped. This is `Completed` unless the program
+    /// exited or faulted before.
+    pub fn stop(self) -> RunOutcome {
+        drop(self.keys);
+        drop(self.frames);
+        self.thread
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::{thread, time::Duration};
+/// Chainable alternative to filling in an `InterpreterConfig` by hand.
+#[derive(Default)]
+struct InterpreterBuilder {
+    config: InterpreterConfig,
+    rom: Option<Vec<u8>>,
+    clock: Option<Box<dyn Clock>>,
+}
 
-    const PROGRAM: [u8; 10] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+impl InterpreterBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-    #[test]
-    fn load_program() {
-        let mut interpreter = Interpreter::new();
-        interpreter.load_program(&PROGRAM);
+    pub fn rom(mut self, bytes: &[u8]) -> Self {
+        self.rom = Some(bytes.to_vec());
+        self
+    }
+
+    pub fn clock_hz(mut self, clock_hz: u32) -> Self {
+        self.config.clock_hz = clock_hz;
+        self
+    }
+
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.config.quirks = quirks;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.config.rng_seed = Some(seed);
+        self
+    }
 
-        for i in 0..PROGRAM.len() {
-            assert_eq!(interpreter.memory[PC_START_ADDRESS + i], PROGRAM[i])
+    pub fn clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    pub fn build(self) -> Interpreter {
+        let clock = self.clock.unwrap_or_else(|| Box::new(SystemClock));
+        let mut interpreter = Interpreter::with_clock(self.config, clock);
+        if let Some(rom) = self.rom {
+            interpreter.load_program(&rom);
         }
+        interpreter
     }
+}
 
-    #[test]
-    fn fetch_instruction() {
-        let mut interpreter = Interpreter::new();
-        interpreter.load_program(&PROGRAM);
+/// Called with opcodes the interpreter can't decode. Returns whether it handled the opcode.
+type CustomHandler = Box<dyn FnMut(&mut Interpreter, u16) -> bool + Send>;
+/// Returns the byte read for `IoAccess::Read`. The return value of writes is ignored.
+type IoHandler = Box<dyn FnMut(IoAccess) -> u8 + Send>;
 
-        assert_eq!(interpreter.fetch_instruction(), 1);
-        assert_eq!(interpreter.fetch_instruction(), 0b0000001000000011);
-        assert_eq!(interpreter.fetch_instruction(), 0b0000010000000101);
-        assert_eq!(interpreter.fetch_instruction(), 0b0000011000000111);
-        assert_eq!(interpreter.fetch_instruction(), 0b0000100000001001);
+/// An opcode touching a memory-mapped I/O port.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum IoAccess {
+    Read(usize),
+    Write(usize, u8),
+}
+
+struct Interpreter {
+    config: InterpreterConfig,
+    pc: usize,
+    i: usize,
+    /// Whether an instruction has set `i` since power-on.
+    i_set: bool,
+    stack: Stack,
+    memory: [u8; MEMORY_SIZE],
+    /// Length of the program loaded at `PC_START_ADDRESS`.
+    program_len: usize,
+    registers: [u8; 16],
+    timers: Timers,
+    rng: Rng,
+    keys: [bool; 16],
+    /// Keys that went down since the last step, one bit per key.
+    just_pressed: u16,
+    key_events: VecDeque<KeyEvent>,
+    /// Keys `EX9E`/`EXA1` see for the rest of the frame with `latch_keys`, one bit per key.
+    latched_keys: Option<u16>,
+    input_recorder: Option<InputRecorder>,
+    input_replay: Option<InputReplay>,
+    /// Sized for hi-res, only the first `display_size` pixels are in use.
+    screen_buffer: [u8; HIRES_WIDTH * HIRES_HEIGHT],
+    /// Phosphor-style afterglow for rendering. Lit pixels are at 255 and fade out once turned off.
+    fade_buffer: [u8; HIRES_WIDTH * HIRES_HEIGHT],
+    /// Bit mask of the planes that drawing and clearing currently affect.
+    planes: u8,
+    /// Whether `00FF` switched to 128x64 mode.
+    hires: bool,
+    /// Set by instructions that touch the screen, see `step_result`.
+    screen_changed: bool,
+    /// Run without frame throttling, advancing the timers by emulated instead of wall-clock time.
+    turbo: bool,
+    opcode_stats: Option<[u64; 16]>,
+    write_watch: Option<BTreeSet<usize>>,
+    watchpoints: Vec<WatchTarget>,
+    custom_handler: Option<CustomHandler>,
+    io_ports: Vec<(Range<usize>, IoHandler)>,
+    on_frame: Option<Box<dyn FnMut() + Send>>,
+    on_scanline: Option<Box<dyn FnMut(usize, u128) + Send>>,
+    beeper: Option<Box<dyn Beeper>>,
+    /// Whether the beeper was last told to play.
+    beeping: bool,
+    /// XO-CHIP sound, loaded by `F002` and played at a rate set by `FX3A`.
+    audio_pattern: [u8; 16],
+    pitch: u8,
+    hit_unimplemented: bool,
+    last_fault: Option<Fault>,
+    /// Number of instructions fetched since power-on.
+    cycles: u64,
+    /// Emulated time the frames run so far took, see `emulated_time`.
+    emulated_time: Duration,
+    /// Address, cycle and frame of the last `DXYN`.
+    last_draw: Option<(usize, u64, u64)>,
+    /// Display area covered by the last `DXYN`, see `last_draw_bounds`.
+    last_draw_bounds: Option<(usize, usize, usize, usize)>,
+    /// Display pixels the last `DXYN` collided on, see `last_collision_mask`.
+    collision_mask: [bool; HIRES_WIDTH * HIRES_HEIGHT],
+    /// Start cycle and calls minus returns of the current stack imbalance window.
+    stack_window: (u64, isize),
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::with_config(InterpreterConfig::default())
     }
 
-    #[test]
-    fn instruction_from_raw() {
-        assert_eq!(Instruction::from_raw(0x00E0), Instruction::ClearScreen);
-        assert_eq!(Instruction::from_raw(0x1FFF), Instruction::Jump(0x0FFF));
-        assert_eq!(
-            Instruction::from_raw(0x6502),
-            Instruction::SetRegister(5, 2)
-        );
-        assert_eq!(
-            Instruction::from_raw(0x70FF),
-            Instruction::AddToRegister(0, 0xFF)
-        );
-        assert_eq!(Instruction::from_raw(0xAFFF), Instruction::SetI(0x0FFF));
-        assert_eq!(
-            Instruction::from_raw(0xD123),
-            Instruction::DrawSprite(1, 2, 3)
+    pub fn builder() -> InterpreterBuilder {
+        InterpreterBuilder::new()
+    }
+
+    pub fn with_config(config: InterpreterConfig) -> Self {
+        Self::with_clock(config, Box::new(SystemClock))
+    }
+
+    pub fn with_clock(config: InterpreterConfig, clock: Box<dyn Clock>) -> Self {
+        let font_address = config.font_address;
+        assert!(
+            font_address + FONT.len() <= PC_START_ADDRESS,
+            "the font must fit below {PC_START_ADDRESS:#05X}"
         );
+        let mut memory = [config.fill_pattern; MEMORY_SIZE];
+        memory[font_address..font_address + FONT.len()].copy_from_slice(&FONT);
+        assert!(config.clock_hz > 0, "the clock rate must not be 0");
+        assert!(config.timer_hz > 0, "the timer rate must not be 0");
+        let mut timers = Timers::with_clock(clock);
+        timers.accumulator = config.timer_accumulator;
+        timers.frequency = config.timer_hz;
+
+        Self {
+            pc: PC_START_ADDRESS,
+            i: 0,
+            i_set: false,
+            stack: Stack::with_depth(config.stack_depth),
+            memory,
+            program_len: 0,
+            registers: [config.fill_pattern; 16],
+            timers,
+            rng: Rng::new(config.rng_seed.unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |time| time.as_nanos() as u64)
+            })),
+            keys: [false; 16],
+            just_pressed: 0,
+            key_events: VecDeque::new(),
+            latched_keys: None,
+            input_recorder: None,
+            input_replay: None,
+            screen_buffer: [PIXEL_OFF; HIRES_WIDTH * HIRES_HEIGHT],
+            fade_buffer: [0; HIRES_WIDTH * HIRES_HEIGHT],
+            planes: 1,
+            hires: false,
+            screen_changed: false,
+            turbo: false,
+            opcode_stats: None,
+            write_watch: None,
+            watchpoints: Vec::new(),
+            custom_handler: None,
+            io_ports: Vec::new(),
+            on_frame: None,
+            on_scanline: None,
+            beeper: None,
+            beeping: false,
+            audio_pattern: [0; 16],
+            pitch: DEFAULT_PITCH,
+            hit_unimplemented: false,
+            last_fault: None,
+            cycles: 0,
+            emulated_time: Duration::ZERO,
+            last_draw: None,
+            last_draw_bounds: None,
+            collision_mask: [false; HIRES_WIDTH * HIRES_HEIGHT],
+            stack_window: (0, 0),
+            diagnostics: Vec::new(),
+            config,
+        }
     }
 
-    #[test]
-    fn nibble() {
-        let yummy = 0x1234;
-        assert_eq!(Instruction::nibble_left(yummy, 0), 1);
-        assert_eq!(Instruction::nibble_left(yummy, 1), 2);
-        assert_eq!(Instruction::nibble_left(yummy, 2), 3);
-        assert_eq!(Instruction::nibble_left(yummy, 3), 4);
+    /// Sets up the interpreter the way the community quirk test ROMs expect for plain CHIP-8:
+    /// COSMAC VIP quirks, which among others make `BNNN` add `V0`, a fixed random seed and a
+    /// clock that only moves when the timers are advanced in turbo mode, so runs are
+    /// reproducible in CI.
+    pub fn for_test_rom() -> Self {
+        let config = InterpreterConfig {
+            quirks: Quirks::cosmac_vip(),
+            rng_seed: Some(TEST_ROM_SEED),
+            ..Default::default()
+        };
+        Self::with_clock(config, Box::new(MockClock::new()))
     }
 
-    #[test]
-    fn stack_pushing_and_popping() {
-        let mut stack = Stack::new();
-        stack.push(10);
-        stack.push(20);
-        assert_eq!(stack.pop(), Some(20));
-        stack.push(30);
-        assert_eq!(stack.pop(), Some(30));
-        assert_eq!(stack.pop(), Some(10));
-        assert_eq!(stack.pop(), None);
+    /// Installs a hook for opcodes that decode to `Unknown`, for experimenting with
+    /// CHIP-8 variants. `pc` already points past the opcode when the handler runs.
+    /// If the handler returns `false`, the configured `UnimplementedPolicy` applies.
+    pub fn set_custom_handler(
+        &mut self,
+        handler: impl FnMut(&mut Interpreter, u16) -> bool + Send + 'static,
+    ) {
+        self.custom_handler = Some(Box::new(handler));
     }
 
-    #[test]
-    fn decrement_timers() {
-        let mut timers = Timers::new();
-        timers.delay_timer = 90;
-        timers.sound_timer = 30;
-        thread::sleep(Duration::from_millis(500));
-        timers.decrement_timers();
-        assert!(approx_equal_u8(timers.delay_timer, 60, 1));
-        assert!(approx_equal_u8(timers.sound_timer, 0, 1));
-        thread::sleep(Duration::from_millis(500));
-        timers.decrement_timers();
-        timers.decrement_timers();
-        assert!(approx_equal_u8(timers.delay_timer, 30, 1));
-        assert!(approx_equal_u8(timers.sound_timer, 0, 1));
+    fn run_custom_handler(&mut self, opcode: u16) -> bool {
+        let Some(mut handler) = self.custom_handler.take() else {
+            return false;
+        };
+        let handled = handler(self, opcode);
+        // The handler may have replaced itself.
+        self.custom_handler.get_or_insert(handler);
+        handled
+    }
+
+    /// Called after every `DXYN` once for each display row the sprite covered, with the lit
+    /// pixels of the whole row as bits, pixel `x` being bit `x`. Meant for cosmetic effects
+    /// like CRT scanlines.
+    pub fn set_on_scanline(&mut self, on_scanline: impl FnMut(usize, u128) + Send + 'static) {
+        self.on_scanline = Some(Box::new(on_scanline));
+    }
+
+    /// Called by `tick_frame` once for every timer frame that passed, `timer_hz` times a second,
+    /// so a front-end can repaint exactly once per CHIP-8 frame.
+    pub fn set_on_frame(&mut self, on_frame: impl FnMut() + Send + 'static) {
+        self.on_frame = Some(Box::new(on_frame));
+    }
+
+    /// Number of full timer frames since the interpreter was created, `timer_hz` per second.
+    pub fn frames(&self) -> u64 {
+        self.timers.frames
+    }
+
+    /// Whether an opcode without a handler was fetched since the flag was last cleared,
+    /// regardless of the `UnimplementedPolicy`.
+    pub fn hit_unimplemented(&self) -> bool {
+        self.hit_unimplemented
+    }
+
+    pub fn clear_hit_unimplemented(&mut self) {
+        self.hit_unimplemented = false;
+    }
+
+    /// The most recent fault `step` ran into, also through the run loops, until cleared.
+    pub fn last_fault(&self) -> Option<&Fault> {
+        self.last_fault.as_ref()
+    }
+
+    pub fn clear_fault(&mut self) {
+        self.last_fault = None;
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// How long the frames run since power-on took on the emulated machine, regardless of
+    /// throttling or turbo mode. Instructions stepped outside of a frame don't count.
+    pub fn emulated_time(&self) -> Duration {
+        self.emulated_time
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// The `(x, y, width, height)` rectangle in display pixels that the last `DXYN` covered, for
+    /// debug overlays. Clipped sprites are cut at the edges, wrapped ones extend past them.
+    pub fn last_draw_bounds(&self) -> Option<(usize, usize, usize, usize)> {
+        self.last_draw_bounds
+    }
+
+    /// One entry per display pixel, row by row, set where the last `DXYN` turned a pixel off.
+    /// Lets debuggers highlight collisions instead of only knowing `VF` was set.
+    pub fn last_collision_mask(&self) -> &[bool] {
+        let (width, height) = self.display_size();
+        &self.collision_mask[..width * height]
+    }
+
+    pub fn set_turbo(&mut self, turbo: bool) {
+        self.turbo = turbo;
+    }
+
+    /// Keys past 0xF don't exist on the keypad and are ignored.
+    pub fn set_key(&mut self, key: usize, pressed: bool) {
+        if key >= self.keys.len() {
+            return;
+        }
+        if pressed && !self.keys[key] {
+            self.just_pressed |= 1 << key;
+        }
+        self.keys[key] = pressed;
+    }
+
+    /// Updates all 16 keys at once, bit `n` being key `n`.
+    pub fn set_keys_bitmask(&mut self, mask: u16) {
+        for key in 0..16 {
+            self.set_key(key, mask & (1 << key) != 0);
+        }
+    }
+
+    pub fn keys_bitmask(&self) -> u16 {
+        self.keys
+            .iter()
+            .enumerate()
+            .fold(0, |mask, (key, pressed)| mask | ((*pressed as u16) << key))
+    }
+
+    /// Queues a key event from the front-end. Events are applied in order before the next step.
+    /// Events for keys past 0xF are dropped.
+    pub fn push_key_event(&mut self, event: KeyEvent) {
+        let (KeyEvent::Down(key) | KeyEvent::Up(key)) = event;
+        if (key as usize) < self.keys.len() {
+            self.key_events.push_back(event);
+        }
+    }
+
+    fn apply_key_event(&mut self, event: KeyEvent) {
+        match event {
+            KeyEvent::Down(key) => {
+                let key = key as usize;
+                if self.keys[key] && !self.config.ignore_key_repeat {
+                    self.just_pressed |= 1 << key;
+                }
+                self.set_key(key, true);
+            }
+            KeyEvent::Up(key) => self.set_key(key as usize, false),
+        }
+    }
+
+    /// Applies the queued key events and latches every key that was down in between.
+    fn latch_keys(&mut self) {
+        let mut latched = self.keys_bitmask();
+        while let Some(event) = self.key_events.pop_front() {
+            self.apply_key_event(event);
+            latched |= self.keys_bitmask();
+        }
+        self.latched_keys = Some(latched);
+    }
+
+    /// Whether `key` counts as held for `EX9E`/`EXA1`.
+    fn key_held(&self, key: usize) -> bool {
+        match self.latched_keys {
+            Some(latched) => latched & (1 << key) != 0,
+            None => self.keys[key],
+        }
+    }
+
+    /// Starts logging the key input seen by every step, see `InputRecorder`.
+    pub fn start_recording(&mut self) {
+        self.input_recorder = Some(InputRecorder::new());
+    }
+
+    pub fn stop_recording(&mut self) -> Option<InputRecorder> {
+        self.input_recorder.take()
+    }
+
+    /// Replays a recording from the current cycle on. Call this on a fresh interpreter built
+    /// with the same config, seed and clock as the recorded one.
+    pub fn replay(&mut self, recording: &InputRecorder) {
+        self.input_replay = Some(InputReplay::new(recording));
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.input_replay
+            .as_ref()
+            .is_some_and(|replay| !replay.is_finished())
+    }
+
+    fn sync_input_log(&mut self) {
+        if let Some(replay) = &mut self.input_replay {
+            if let Some(input) = replay.next_for(self.cycles) {
+                for key in 0..16 {
+                    self.keys[key] = input.keys & (1 << key) != 0;
+                }
+                self.just_pressed = input.just_pressed;
+            }
+        }
+        let keys = self.keys_bitmask();
+        if let Some(recorder) = &mut self.input_recorder {
+            recorder.record(self.cycles, keys, self.just_pressed);
+        }
+    }
+
+    /// Starts counting executed instructions by their high nibble.
+    /// Profiling is off by default, in which case `step` doesn't touch the histogram at all.
+    pub fn enable_profiling(&mut self) {
+        self.opcode_stats.get_or_insert([0; 16]);
+    }
+
+    /// Executed instruction counts indexed by opcode high nibble, e.g. `[1]` counts jumps.
+    pub fn opcode_stats(&self) -> Option<&[u64; 16]> {
+        self.opcode_stats.as_ref()
+    }
+
+    /// The opcode histogram as text for external tools, keyed by the high nibble in hex.
+    /// Without profiling every count is zero.
+    pub fn export_opcode_stats(&self, format: StatsFormat) -> String {
+        let stats = self.opcode_stats.unwrap_or_default();
+        let counts = stats.iter().enumerate();
+        match format {
+            StatsFormat::Csv => counts
+                .fold("opcode,count\n".to_string(), |csv, (nibble, count)| {
+                    csv + &format!("{nibble:X},{count}\n")
+                }),
+            StatsFormat::Json => {
+                let entries: Vec<String> = counts
+                    .map(|(nibble, count)| format!("\"{nibble:X}\":{count}"))
+                    .collect();
+                format!("{{{}}}", entries.join(","))
+            }
+        }
+    }
+
+    /// Hashes the CPU state, timers and screen, so two interpreters that should run in
+    /// lockstep can be checked for a desync without comparing everything. Memory is left out.
+    ///
+    /// The hash is 64-bit FNV-1a over fixed-width little-endian fields, with addresses as 16
+    /// bits, so it's the same across Rust releases and platforms and can be pinned in tests.
+    pub fn state_hash(&self) -> u64 {
+        const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+        let stack = &self.stack.data[..self.stack.position];
+        let bytes = (self.pc as u16)
+            .to_le_bytes()
+            .into_iter()
+            .chain((self.i as u16).to_le_bytes())
+            .chain(self.registers)
+            .chain([stack.len() as u8])
+            .chain(
+                stack
+                    .iter()
+                    .flat_map(|address| (*address as u16).to_le_bytes()),
+            )
+            .chain([self.timers.delay_timer, self.timers.sound_timer])
+            .chain(self.screen().iter().copied());
+        bytes.fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+        })
+    }
+
+    /// Width and height of the display in pixels. With `lores_scaling` the display is always
+    /// hi-res, otherwise it follows the current mode.
+    pub fn display_size(&self) -> (usize, usize) {
+        if self.hires || self.config.quirks.lores_scaling {
+            (HIRES_WIDTH, HIRES_HEIGHT)
+        } else {
+            (SCREEN_WIDTH, SCREEN_HEIGHT)
+        }
+    }
+
+    /// One byte per pixel, row by row, `display_size` wide. Each byte holds a bit per plane,
+    /// so `PIXEL_OFF` is off. The whole screen starts out off, in either resolution.
+    pub fn screen(&self) -> &[u8] {
+        let (width, height) = self.display_size();
+        &self.screen_buffer[..width * height]
+    }
+
+    /// One byte per pixel like `screen`, but only for plane `index`, 1 where it's lit, for
+    /// front-ends that color each XO-CHIP plane separately. Panics if `index` isn't a plane.
+    pub fn plane(&self, index: usize) -> Vec<u8> {
+        assert!(index < PLANE_COUNT, "there are only {PLANE_COUNT} planes");
+        self.screen()
+            .iter()
+            .map(|pixel| (pixel >> index) & 1)
+            .collect()
+    }
+
+    /// An owned copy of the screen, for keeping a frame around while the interpreter runs on.
+    pub fn snapshot_screen(&self) -> Vec<u8> {
+        self.screen().to_vec()
+    }
+
+    /// Expands the screen into RGBA bytes at native resolution, lit pixels in any plane
+    /// becoming `on`. The configured `DisplayTransform` is applied on the way.
+    pub fn frame_buffer_rgba(&self, on: [u8; 4], off: [u8; 4]) -> Vec<u8> {
+        let (width, height) = self.display_size();
+        let (flip_x, flip_y) = match self.config.display_transform {
+            DisplayTransform::Normal => (false, false),
+            DisplayTransform::FlipX => (true, false),
+            DisplayTransform::FlipY => (false, true),
+            DisplayTransform::Rotate180 => (true, true),
+        };
+        let screen = self.screen();
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .flat_map(|(x, y)| {
+                let x = if flip_x { width - 1 - x } else { x };
+                let y = if flip_y { height - 1 - y } else { y };
+                if screen[y * width + x] != 0 {
+                    on
+                } else {
+                    off
+                }
+            })
+            .collect()
+    }
+
+    /// Attaches an audio output that is switched on while the sound timer runs.
+    pub fn set_beeper(&mut self, beeper: impl Beeper + 'static) {
+        self.beeper = Some(Box::new(beeper));
+        self.beeping = false;
+        self.update_beeper();
+    }
+
+    /// Tells the beeper about the sound timer starting or stopping, but not every frame
+    /// in between.
+    fn update_beeper(&mut self) {
+        let playing = self.timers.sound_timer > 0;
+        if let Some(beeper) = &mut self.beeper {
+            if playing != self.beeping {
+                beeper.set_playing(playing);
+            }
+        }
+        self.beeping = playing;
+    }
+
+    /// Samples for the rest of the current beep, i.e. as long as the sound timer stays active.
+    pub fn beep_samples(&self, sample_rate: u32, freq: f32, waveform: Waveform) -> Vec<f32> {
+        audio::generate_beep_samples(sample_rate, self.timers.sound_timer as u32, freq, waveform)
+    }
+
+    /// The XO-CHIP audio pattern last loaded with `F002`, 128 bits of 1-bit audio.
+    pub fn audio_pattern(&self) -> &[u8; 16] {
+        &self.audio_pattern
+    }
+
+    /// The XO-CHIP pitch register, see `audio::pattern_playback_rate`.
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    /// Like `beep_samples`, but plays the audio pattern at the current pitch instead of a tone.
+    pub fn pattern_samples(&self, sample_rate: u32) -> Vec<f32> {
+        audio::generate_pattern_samples(
+            sample_rate,
+            self.timers.sound_timer as u32,
+            &self.audio_pattern,
+            audio::pattern_playback_rate(self.pitch),
+        )
+    }
+
+    /// Per-pixel brightness for front-ends that want to soften XOR flicker.
+    /// Collision detection only ever looks at the screen buffer.
+    pub fn fade_buffer(&self) -> &[u8] {
+        let (width, height) = self.display_size();
+        &self.fade_buffer[..width * height]
+    }
+
+    fn update_fade(&mut self) {
+        let Some(decay) = self.config.fade_decay else {
+            return;
+        };
+        for (fade, pixel) in self.fade_buffer.iter_mut().zip(self.screen_buffer.iter()) {
+            *fade = if *pixel != 0 {
+                u8::MAX
+            } else {
+                fade.saturating_sub(decay)
+            };
+        }
+    }
+
+    /// Copies `bytes` into memory at `PC_START_ADDRESS`.
+    ///
+    /// An empty program is accepted and leaves memory as it is. With zeroed memory the first
+    /// fetch then reads `0x0000`, which doesn't decode, so running faults right away with
+    /// `Fault::DecodeError` under the default `UnimplementedPolicy`. Use `verify_rom` to reject
+    /// empty ROMs up front instead.
+    pub fn load_program(&mut self, bytes: &[u8]) {
+        for (i, byte) in bytes.iter().enumerate() {
+            self.memory[PC_START_ADDRESS + i] = *byte;
+        }
+        self.program_len = bytes.len();
+    }
+
+    /// Copies `bytes` into memory at `address`, e.g. to set up sprite data in tests. Unlike
+    /// opcode writes this ignores `protect_reserved`, mapped I/O and the write watch.
+    pub fn set_memory(&mut self, address: usize, bytes: &[u8]) -> Result<(), MemError> {
+        let end = address
+            .checked_add(bytes.len())
+            .ok_or(MemError::OutOfBounds(address))?;
+        let target = self
+            .memory
+            .get_mut(address..end)
+            .ok_or(MemError::OutOfBounds(address))?;
+        target.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Writes `opcode` big-endian at `address`, for poking instructions into a running program.
+    /// Like `set_memory`, this bypasses all write checks.
+    pub fn patch(&mut self, address: usize, opcode: u16) -> Result<(), MemError> {
+        self.set_memory(address, &opcode.to_be_bytes())
+    }
+
+    /// Renders `rows` bytes of sprite data at `address` as lines of `#` and `.`, for seeing
+    /// what a ROM is about to draw.
+    pub fn sprite_ascii(&self, address: usize, rows: u8) -> Result<String, MemError> {
+        let end = address
+            .checked_add(rows as usize)
+            .ok_or(MemError::OutOfBounds(address))?;
+        let sprite = self
+            .memory
+            .get(address..end)
+            .ok_or(MemError::OutOfBounds(address))?;
+        Ok(sprite
+            .iter()
+            .map(|byte| {
+                let mut line: String = (0..8)
+                    .map(|bit| if byte & (0x80 >> bit) != 0 { '#' } else { '.' })
+                    .collect();
+                line.push('\n');
+                line
+            })
+            .collect())
+    }
+
+    /// Reads a whole ROM from `reader` and loads it. ROMs that don't fit in memory are rejected
+    /// with `InvalidData` without reading past the limit.
+    pub fn load_program_from_reader(&mut self, reader: impl Read) -> io::Result<()> {
+        let limit = MEMORY_SIZE - PC_START_ADDRESS;
+        let mut bytes = Vec::new();
+        reader.take(limit as u64 + 1).read_to_end(&mut bytes)?;
+        if bytes.len() > limit {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the ROM is larger than the {limit} bytes available"),
+            ));
+        }
+        self.load_program(&bytes);
+        Ok(())
+    }
+
+    /// Loads a gzip-compressed ROM from `path`, with the same size limit as
+    /// `load_program_from_reader`, which also stops decompressing once it's exceeded.
+    #[cfg(feature = "gzip")]
+    pub fn load_program_gz(&mut self, path: impl AsRef<std::path::Path>) -> io::Result<()> {
+        let file = std::fs::File::open(path)?;
+        self.load_program_from_reader(flate2::read::GzDecoder::new(file))
+    }
+
+    /// Checks that a ROM can be loaded and guesses which platform it was written for by looking
+    /// for SUPER-CHIP opcodes. Like `compatibility_check`, this scans every aligned word, so it
+    /// can be fooled by data that happens to look like one.
+    pub fn verify_rom(bytes: &[u8]) -> Result<RomInfo, RomError> {
+        if bytes.is_empty() {
+            return Err(RomError::Empty);
+        }
+        if bytes.len() > MEMORY_SIZE - PC_START_ADDRESS {
+            return Err(RomError::TooLarge(bytes.len()));
+        }
+
+        let super_chip = bytes.chunks_exact(2).any(|word| {
+            matches!(
+                Instruction::from_raw(((word[0] as u16) << 8) | word[1] as u16),
+                Instruction::ScrollDown(_)
+                    | Instruction::ScrollRight
+                    | Instruction::ScrollLeft
+                    | Instruction::Exit
+                    | Instruction::LowRes
+                    | Instruction::HighRes
+                    | Instruction::DrawLargeSprite(..)
+                    | Instruction::LargeFontCharacter(_)
+                    | Instruction::StoreFlags(_)
+                    | Instruction::LoadFlags(_)
+            )
+        });
+        Ok(RomInfo {
+            size: bytes.len(),
+            super_chip,
+            suggested_quirks: if super_chip {
+                Quirks::super_chip()
+            } else {
+                Quirks::cosmac_vip()
+            },
+        })
+    }
+
+    /// What the interpreter decodes, for tooling and documentation. Opcodes that only decode so
+    /// far fault with `Fault::Unsupported`, their descriptions say so.
+    pub fn supported_opcodes() -> &'static [OpcodeInfo] {
+        &SUPPORTED_OPCODES
+    }
+
+    /// Lists the address and opcode of every word in `bytes` that the interpreter can't decode.
+    ///
+    /// This is a static scan over every aligned word, so sprite data and other non-code bytes
+    /// will show up as false positives. A trailing odd byte is ignored.
+    pub fn compatibility_check(bytes: &[u8]) -> Vec<(usize, u16)> {
+        bytes
+            .chunks_exact(2)
+            .enumerate()
+            .map(|(index, word)| {
+                let address = PC_START_ADDRESS + index * 2;
+                (address, ((word[0] as u16) << 8) | word[1] as u16)
+            })
+            .filter(|(_, opcode)| matches!(Instruction::from_raw(*opcode), Instruction::Unknown(_)))
+            .collect()
+    }
+
+    /// Builds a rough call graph from the `2NNN` calls in `bytes`, mapping each subroutine to the
+    /// sorted subroutines it calls. The entry point counts as a subroutine, and every call is
+    /// attributed to the closest call target at or before it, since the end of a subroutine
+    /// can't be known statically.
+    ///
+    /// Like `compatibility_check`, this scans every aligned word, so data that happens to look
+    /// like a call adds false edges.
+    pub fn call_graph(bytes: &[u8]) -> HashMap<usize, Vec<usize>> {
+        let calls: Vec<(usize, usize)> = bytes
+            .chunks_exact(2)
+            .enumerate()
+            .filter_map(|(index, word)| {
+                match Instruction::from_raw(((word[0] as u16) << 8) | word[1] as u16) {
+                    Instruction::Call(target) => Some((PC_START_ADDRESS + index * 2, target)),
+                    _ => None,
+                }
+            })
+            .collect();
+        let subroutines: BTreeSet<usize> = calls
+            .iter()
+            .map(|(_, target)| *target)
+            .chain([PC_START_ADDRESS])
+            .collect();
+
+        let mut graph: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (address, target) in calls {
+            let caller = subroutines
+                .range(..=address)
+                .next_back()
+                .copied()
+                .unwrap_or(PC_START_ADDRESS);
+            let callees = graph.entry(caller).or_default();
+            if let Err(index) = callees.binary_search(&target) {
+                callees.insert(index, target);
+            }
+        }
+        graph
+    }
+
+    /// Puts the machine back into its power-on state. The configuration, clock, turbo mode and
+    /// installed hooks survive, and profiling or write watching stay enabled but start over.
+    pub fn reset(&mut self) {
+        let clock = mem::replace(&mut self.timers.clock, Box::new(SystemClock));
+        let mut fresh = Self::with_clock(self.config.clone(), clock);
+        fresh.turbo = self.turbo;
+        fresh.opcode_stats = self.opcode_stats.map(|_| [0; 16]);
+        fresh.write_watch = self.write_watch.as_ref().map(|_| BTreeSet::new());
+        fresh.watchpoints = mem::take(&mut self.watchpoints);
+        fresh.custom_handler = self.custom_handler.take();
+        fresh.io_ports = mem::take(&mut self.io_ports);
+        fresh.on_frame = self.on_frame.take();
+        fresh.on_scanline = self.on_scanline.take();
+        fresh.beeper = self.beeper.take();
+        // Silences a beep that was still going.
+        fresh.beeping = self.beeping;
+        fresh.update_beeper();
+        *self = fresh;
+    }
+
+    /// Clears both timers and stops a beep without touching the rest of the machine, e.g. when
+    /// the front-end mutes.
+    pub fn reset_timers(&mut self) {
+        self.timers.reset();
+        self.update_beeper();
+    }
+
+    /// Resets the machine and loads another program in its place.
+    pub fn swap_program(&mut self, bytes: &[u8]) {
+        self.reset();
+        self.load_program(bytes);
+    }
+
+    /// The loaded program as it currently is in memory, without the font and reserved area.
+    pub fn program_bytes(&self) -> &[u8] {
+        &self.memory[PC_START_ADDRESS..PC_START_ADDRESS + self.program_len]
+    }
+
+    /// Bytes left between the end of the loaded program and the end of memory, for rejecting
+    /// patches or appended code that wouldn't fit before writing any of it.
+    pub fn free_program_space(&self) -> usize {
+        MEMORY_SIZE - PC_START_ADDRESS - self.program_len
+    }
+
+    /// Serializes the loaded program together with the clock and quirks it should run with.
+    /// The ROM is taken from memory, so save before running self-modifying programs.
+    ///
+    /// The format is a `chip8-bundle` header line followed by `key=value` lines.
+    /// Readers skip keys they don't know, so newer fields can be added freely.
+    pub fn save_bundle(&self) -> String {
+        let quirks = &self.config.quirks;
+        let rom: String = self
+            .program_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect();
+
+        format!(
+            "{BUNDLE_HEADER}\n\
+             clock_hz={}\n\
+             quirks.memory_increments_i={}\n\
+             quirks.clip_x={}\n\
+             quirks.clip_y={}\n\
+             quirks.lores_scaling={}\n\
+             quirks.scroll_wraps={}\n\
+             quirks.shift_uses_vy={}\n\
+             quirks.jump_uses_vx={}\n\
+             rom={rom}\n",
+            self.config.clock_hz,
+            quirks.memory_increments_i,
+            quirks.clip_x,
+            quirks.clip_y,
+            quirks.lores_scaling,
+            quirks.scroll_wraps,
+            quirks.shift_uses_vy,
+            quirks.jump_uses_vx,
+        )
+    }
+
+    pub fn load_bundle(bundle: &str) -> Result<Self, BundleError> {
+        let mut lines = bundle.lines();
+        if lines.next().map(str::trim) != Some(BUNDLE_HEADER) {
+            return Err(BundleError::MissingHeader);
+        }
+
+        let mut config = InterpreterConfig::default();
+        let mut rom = None;
+        for line in lines {
+            let Some((key, value)) = line.trim().split_once('=') else {
+                continue;
+            };
+            let invalid = || BundleError::InvalidValue(key.to_string());
+            match key {
+                "clock_hz" => match value.parse() {
+                    Ok(0) | Err(_) => return Err(invalid()),
+                    Ok(clock_hz) => config.clock_hz = clock_hz,
+                },
+                "quirks.memory_increments_i" => {
+                    config.quirks.memory_increments_i = value.parse().map_err(|_| invalid())?
+                }
+                // Bundles written before the axes were split clip both or neither.
+                "quirks.clipping" => {
+                    let clipping = value.parse().map_err(|_| invalid())?;
+                    config.quirks.clip_x = clipping;
+                    config.quirks.clip_y = clipping;
+                }
+                "quirks.clip_x" => config.quirks.clip_x = value.parse().map_err(|_| invalid())?,
+                "quirks.clip_y" => config.quirks.clip_y = value.parse().map_err(|_| invalid())?,
+                "quirks.lores_scaling" => {
+                    config.quirks.lores_scaling = value.parse().map_err(|_| invalid())?
+                }
+                "quirks.scroll_wraps" => {
+                    config.quirks.scroll_wraps = value.parse().map_err(|_| invalid())?
+                }
+                "quirks.shift_uses_vy" => {
+                    config.quirks.shift_uses_vy = value.parse().map_err(|_| invalid())?
+                }
+                "quirks.jump_uses_vx" => {
+                    config.quirks.jump_uses_vx = value.parse().map_err(|_| invalid())?
+                }
+                "rom" => {
+                    // Checked up front so slicing by bytes can't split a character and the ROM
+                    // is known to fit before it's loaded.
+                    if !value.is_ascii()
+                        || value.len() % 2 != 0
+                        || value.len() / 2 > MEMORY_SIZE - PC_START_ADDRESS
+                    {
+                        return Err(invalid());
+                    }
+                    let bytes: Result<Vec<u8>, _> = (0..value.len())
+                        .step_by(2)
+                        .map(|i| u8::from_str_radix(&value[i..i + 2], 16))
+                        .collect();
+                    rom = Some(bytes.map_err(|_| invalid())?);
+                }
+                _ => {}
+            }
+        }
+
+        let rom = rom.ok_or(BundleError::MissingRom)?;
+        let mut interpreter = Self::with_config(config);
+        interpreter.load_program(&rom);
+        Ok(interpreter)
+    }
+
+    /// Starts recording addresses inside the loaded program that instructions write to,
+    /// which is how self-modifying ROMs show up.
+    pub fn enable_write_watch(&mut self) {
+        self.write_watch.get_or_insert_with(BTreeSet::new);
+    }
+
+    pub fn modified_addresses(&self) -> Option<&BTreeSet<usize>> {
+        self.write_watch.as_ref()
+    }
+
+    /// The address `offset` bytes past `i`, wrapped around the end of memory if configured.
+    fn i_address(&self, offset: usize) -> usize {
+        if self.config.i_wraps {
+            (self.i + offset) % MEMORY_SIZE
+        } else {
+            self.i + offset
+        }
+    }
+
+    /// Routes opcode reads and writes of `range` to `handler` instead of memory, for building
+    /// custom peripherals. Instruction fetches still see memory, and standard ROMs aren't
+    /// affected unless they happen to touch the range. The first mapping of an address wins.
+    pub fn map_io(
+        &mut self,
+        range: Range<usize>,
+        handler: impl FnMut(IoAccess) -> u8 + Send + 'static,
+    ) {
+        self.io_ports.push((range, Box::new(handler)));
+    }
+
+    fn io_port(&mut self, address: usize) -> Option<&mut IoHandler> {
+        self.io_ports
+            .iter_mut()
+            .find(|(range, _)| range.contains(&address))
+            .map(|(_, handler)| handler)
+    }
+
+    fn read_memory(&mut self, address: usize) -> Result<u8, Fault> {
+        if let Some(handler) = self.io_port(address) {
+            return Ok(handler(IoAccess::Read(address)));
+        }
+        self.memory
+            .get(address)
+            .copied()
+            .ok_or(Fault::MemoryOutOfBounds(address))
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), Fault> {
+        if let Some(handler) = self.io_port(address) {
+            handler(IoAccess::Write(address, value));
+            return Ok(());
+        }
+        if self.config.protect_reserved && address < PC_START_ADDRESS {
+            return Err(Fault::WriteToReserved(address));
+        }
+        *self
+            .memory
+            .get_mut(address)
+            .ok_or(Fault::MemoryOutOfBounds(address))? = value;
+
+        let program = PC_START_ADDRESS..PC_START_ADDRESS + self.program_len;
+        if let Some(watch) = &mut self.write_watch {
+            if program.contains(&address) {
+                watch.insert(address);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads register `Vx`, or `None` if `x` doesn't name one of V0..VF.
+    pub fn v(&self, x: usize) -> Option<u8> {
+        self.registers.get(x).copied()
+    }
+
+    pub fn set_v(&mut self, x: usize, value: u8) -> Result<(), Fault> {
+        let register = self.registers.get_mut(x).ok_or(Fault::InvalidRegister(x))?;
+        *register = value;
+        Ok(())
+    }
+
+    /// V0 up to but excluding V`count`, in register order. A `count` over 16 names registers
+    /// past VF and fails with the first of them.
+    pub fn registers_to_slice(&self, count: usize) -> Result<&[u8], Fault> {
+        self.registers
+            .get(..count)
+            .ok_or(Fault::InvalidRegister(self.registers.len()))
+    }
+
+    /// Copies `data` into V0 onwards. Fails without changing anything if `data` is longer
+    /// than 16 bytes.
+    pub fn registers_from_slice(&mut self, data: &[u8]) -> Result<(), Fault> {
+        let len = self.registers.len();
+        self.registers
+            .get_mut(..data.len())
+            .ok_or(Fault::InvalidRegister(len))?
+            .copy_from_slice(data);
+        Ok(())
+    }
+
+    fn fetch_instruction(&mut self) -> u16 {
+        let instruction =
+            ((self.memory[self.pc] as u16) << 8) | self.memory[(self.pc + 1) % MEMORY_SIZE] as u16;
+        self.pc += 2;
+        if self.config.i_wraps {
+            self.pc %= MEMORY_SIZE;
+        }
+        instruction
+    }
+
+    /// Address of the instruction that was fetched last, as long as it didn't jump.
+    fn instruction_address(&self) -> usize {
+        (self.pc + MEMORY_SIZE - 2) % MEMORY_SIZE
+    }
+
+    /// Decodes the word at `address` without executing it or touching any state, for debuggers
+    /// looking at code away from `pc`. Like instruction fetches, this reads memory and not
+    /// mapped I/O.
+    pub fn decode_at(&self, address: usize) -> Result<Instruction, MemError> {
+        let end = address
+            .checked_add(2)
+            .ok_or(MemError::OutOfBounds(address))?;
+        let word = self
+            .memory
+            .get(address..end)
+            .ok_or(MemError::OutOfBounds(address))?;
+        Ok(Instruction::from_raw(
+            ((word[0] as u16) << 8) | word[1] as u16,
+        ))
+    }
+
+    /// Executes the instruction at `pc` and returns it, so callers can trace execution.
+    pub fn step(&mut self) -> Result<Instruction, Fault> {
+        while let Some(event) = self.key_events.pop_front() {
+            self.apply_key_event(event);
+        }
+        self.sync_input_log();
+        let result = self.execute_next();
+        // Presses are only visible to the instruction right after them.
+        self.just_pressed = 0;
+        if let Err(fault) = &result {
+            self.last_fault = Some(fault.clone());
+        }
+        result
+    }
+
+    /// A leaner `step` for benchmarks and ROMs known to be well-behaved. It fetches without
+    /// bounds or alignment checks and skips profiling, the input log and queued key events, so
+    /// use `set_key` for input. Unknown opcodes are skipped like with `UnimplementedPolicy::Skip`.
+    /// Faults while executing are still reported and recorded as `last_fault`.
+    ///
+    /// # Safety
+    ///
+    /// `pc` must be below `MEMORY_SIZE - 1`, so that both bytes of the opcode are in memory.
+    pub unsafe fn step_unchecked(&mut self) -> Result<Instruction, Fault> {
+        // SAFETY: the caller guarantees that `pc` and `pc + 1` are inside memory.
+        let raw = unsafe {
+            ((*self.memory.get_unchecked(self.pc) as u16) << 8)
+                | *self.memory.get_unchecked(self.pc + 1) as u16
+        };
+        self.pc += 2;
+        self.cycles += 1;
+        let instruction = Instruction::from_raw(raw);
+        let result = self.execute(instruction).map(|()| instruction);
+        self.just_pressed = 0;
+        if let Err(fault) = &result {
+            self.last_fault = Some(fault.clone());
+        }
+        result
+    }
+
+    /// Like `step`, but also tells whether the screen needs to be redrawn afterwards.
+    pub fn step_result(&mut self) -> Result<StepResult, Fault> {
+        self.screen_changed = false;
+        let instruction = self.step()?;
+        Ok(StepResult {
+            instruction,
+            screen_changed: self.screen_changed,
+        })
+    }
+
+    fn execute_next(&mut self) -> Result<Instruction, Fault> {
+        if self.config.i_wraps {
+            self.pc %= MEMORY_SIZE;
+        } else if self.pc + 1 >= MEMORY_SIZE {
+            return Err(Fault::PcOutOfBounds(self.pc));
+        }
+        if self.config.require_aligned_pc && self.pc & 1 != 0 {
+            return Err(Fault::MisalignedPc(self.pc));
+        }
+        let raw = self.fetch_instruction();
+        self.cycles += 1;
+        if let Some(stats) = &mut self.opcode_stats {
+            stats[Instruction::nibble_left(raw, 0) as usize] += 1;
+        }
+
+        let instruction = Instruction::from_raw(raw);
+        if instruction == Instruction::Unknown(raw) && !self.run_custom_handler(raw) {
+            self.hit_unimplemented = true;
+            match self.config.unimplemented_policy {
+                UnimplementedPolicy::Fault => {
+                    self.pc = self.instruction_address();
+                    return Err(Fault::DecodeError {
+                        pc: self.pc,
+                        opcode: raw,
+                    });
+                }
+                UnimplementedPolicy::Skip => return Ok(instruction),
+                UnimplementedPolicy::Panic => {
+                    panic!(
+                        "unimplemented opcode {raw:#06X} at {:#05X}",
+                        self.instruction_address()
+                    )
+                }
+            }
+        }
+
+        self.execute(instruction)?;
+        Ok(instruction)
+    }
+
+    /// Executes `opcode` as if it had just been fetched from `pc`, without reading memory, for
+    /// poking single instructions from a REPL. `pc` is advanced past it while it runs, so faults
+    /// and diagnostics report `pc` and calls return to the instruction after it, and is put
+    /// back afterwards unless the instruction jumps, calls, returns or skips. Key waits and
+    /// exits don't rewind, faults leave `pc` where it was, and the cycle counter doesn't move.
+    pub fn exec_raw(&mut self, opcode: u16) -> Result<Instruction, Fault> {
+        let pc = self.pc;
+        self.pc = (pc + 2) % MEMORY_SIZE;
+        let advanced = self.pc;
+        let instruction = Instruction::from_raw(opcode);
+        if instruction == Instruction::Unknown(opcode) && !self.run_custom_handler(opcode) {
+            self.pc = pc;
+            return Err(Fault::DecodeError { pc, opcode });
+        }
+        if let Err(fault) = self.execute(instruction) {
+            self.pc = pc;
+            return Err(fault);
+        }
+        match instruction {
+            Instruction::Jump(_)
+            | Instruction::JumpWithOffset(_)
+            | Instruction::Call(_)
+            | Instruction::Return => {}
+            Instruction::WaitForKey(_) | Instruction::Exit => self.pc = pc,
+            // A custom handler may have jumped itself.
+            Instruction::Unknown(_) if self.pc != advanced => {}
+            // Skips stay one instruction ahead.
+            _ => self.pc = (self.pc + MEMORY_SIZE - 2) % MEMORY_SIZE,
+        }
+        Ok(instruction)
+    }
+
+    /// Steps once, returning the outcome instead of the instruction if running should stop here.
+    fn run_one(&mut self) -> Result<Instruction, RunOutcome> {
+        let watched = self.watched_values();
+        match self.step() {
+            Ok(Instruction::Exit) => Err(RunOutcome::Exited),
+            Ok(instruction) => match self.changed_watchpoint(&watched) {
+                Some(target) => Err(RunOutcome::WatchpointHit(target)),
+                None => Ok(instruction),
+            },
+            Err(fault) => Err(RunOutcome::Fault(fault)),
+        }
+    }
+
+    /// Stops the run loops with `RunOutcome::WatchpointHit` after any step that changes
+    /// `target`. Changes made from outside, e.g. by `set_v`, go unnoticed.
+    pub fn add_watchpoint(&mut self, target: WatchTarget) {
+        if !self.watchpoints.contains(&target) {
+            self.watchpoints.push(target);
+        }
+    }
+
+    pub fn remove_watchpoint(&mut self, target: WatchTarget) {
+        self.watchpoints.retain(|watched| *watched != target);
+    }
+
+    fn watched_values(&self) -> Vec<Option<u8>> {
+        self.watchpoints
+            .iter()
+            .map(|target| match *target {
+                WatchTarget::Register(x) => self.registers.get(x).copied(),
+                WatchTarget::Memory(address) => self.memory.get(address).copied(),
+            })
+            .collect()
+    }
+
+    fn changed_watchpoint(&self, before: &[Option<u8>]) -> Option<WatchTarget> {
+        self.watched_values()
+            .iter()
+            .zip(before)
+            .position(|(after, before)| after != before)
+            .map(|index| self.watchpoints[index])
+    }
+
+    pub fn run_cycles(&mut self, cycles: usize) -> RunOutcome {
+        for _ in 0..cycles {
+            if let Err(outcome) = self.run_one() {
+                return outcome;
+            }
+        }
+        RunOutcome::Completed
+    }
+
+    /// Steps until the instructions' `cycle_cost`s add up to at least `budget`.
+    fn run_machine_cycles(&mut self, budget: u32) -> RunOutcome {
+        let mut spent = 0;
+        while spent < budget {
+            match self.run_one() {
+                Ok(instruction) => spent += instruction.cycle_cost(),
+                Err(outcome) => return outcome,
+            }
+        }
+        RunOutcome::Completed
+    }
+
+    /// Steps until `pc == target`, giving up with `RunOutcome::Completed` after `max_cycles`.
+    pub fn run_until_pc(&mut self, target: usize, max_cycles: usize) -> RunOutcome {
+        for _ in 0..max_cycles {
+            if self.pc == target {
+                return RunOutcome::PcReached;
+            }
+            if let Err(outcome) = self.run_one() {
+                return outcome;
+            }
+        }
+        if self.pc == target {
+            return RunOutcome::PcReached;
+        }
+        RunOutcome::Completed
+    }
+
+    /// Debugger "step over": if `pc` is on a call, runs the whole subroutine and stops with
+    /// `RunOutcome::PcReached` once it returns, giving up after `max_cycles`. Anything else is
+    /// just stepped.
+    pub fn step_over(&mut self, max_cycles: usize) -> RunOutcome {
+        if max_cycles == 0 {
+            return RunOutcome::Completed;
+        }
+        let depth = self.stack.position;
+        let is_call = matches!(self.decode_at(self.pc), Ok(Instruction::Call(_)));
+        if let Err(outcome) = self.run_one() {
+            return outcome;
+        }
+        if !is_call {
+            return RunOutcome::Completed;
+        }
+        self.run_until_stack_depth(depth, max_cycles - 1)
+    }
+
+    /// Debugger "step out": runs until the current subroutine returns to its caller and stops
+    /// there with `RunOutcome::PcReached`, giving up after `max_cycles`. Outside of a
+    /// subroutine there's nothing to step out of and nothing runs.
+    pub fn step_out(&mut self, max_cycles: usize) -> RunOutcome {
+        match self.stack.position.checked_sub(1) {
+            Some(depth) => self.run_until_stack_depth(depth, max_cycles),
+            None => RunOutcome::Completed,
+        }
+    }
+
+    /// Steps until the stack is back down to `depth` entries.
+    fn run_until_stack_depth(&mut self, depth: usize, max_cycles: usize) -> RunOutcome {
+        for _ in 0..max_cycles {
+            if self.stack.position <= depth {
+                return RunOutcome::PcReached;
+            }
+            if let Err(outcome) = self.run_one() {
+                return outcome;
+            }
+        }
+        if self.stack.position <= depth {
+            return RunOutcome::PcReached;
+        }
+        RunOutcome::Completed
+    }
+
+    fn cycles_per_frame(&self) -> usize {
+        (self.config.clock_hz as f32 / self.config.timer_hz as f32).round() as usize
+    }
+
+    fn machine_cycles_per_frame(&self) -> u32 {
+        MACHINE_CYCLES_PER_SECOND / self.config.timer_hz
+    }
+
+    fn frame_duration(&self) -> Duration {
+        Duration::from_secs_f32(1.0 / self.config.timer_hz as f32)
+    }
+
+    /// Executes one timer frame's worth of instructions and updates the timers.
+    pub fn tick_frame(&mut self) -> RunOutcome {
+        let (outcome, emulated) = self.run_frame_cycles();
+        let frames = if self.turbo {
+            self.timers.advance_emulated(emulated)
+        } else {
+            self.timers.decrement_timers()
+        };
+        self.finish_frames(frames);
+        outcome
+    }
+
+    /// Executes one frame's worth of instructions and decrements the timers exactly once,
+    /// no matter how much time passed, for stepping timer-dependent ROMs frame by frame.
+    pub fn advance_one_frame(&mut self) -> RunOutcome {
+        let (outcome, _) = self.run_frame_cycles();
+        self.timers.tick();
+        self.finish_frames(1);
+        outcome
+    }
+
+    /// Runs the instructions of one frame and returns how much emulated time they took.
+    fn run_frame_cycles(&mut self) -> (RunOutcome, Duration) {
+        if self.config.latch_keys {
+            self.latch_keys();
+        }
+        let result = match self.config.timing {
+            Timing::Flat => {
+                let cycles = self.cycles_per_frame();
+                let emulated = Duration::from_secs_f32(cycles as f32 / self.config.clock_hz as f32);
+                (self.run_cycles(cycles), emulated)
+            }
+            Timing::CycleCost => (
+                self.run_machine_cycles(self.machine_cycles_per_frame()),
+                self.frame_duration(),
+            ),
+        };
+        self.latched_keys = None;
+        self.emulated_time += result.1;
+        result
+    }
+
+    fn finish_frames(&mut self, frames: u64) {
+        self.update_beeper();
+        self.update_fade();
+        if let Some(on_frame) = &mut self.on_frame {
+            for _ in 0..frames {
+                on_frame();
+            }
+        }
+    }
+
+    /// Runs `n` frames back to back in turbo mode, so the timers follow emulated time and
+    /// nothing sleeps. Stops early if the program does. Meant for headless benchmarks together
+    /// with `cycles`.
+    pub fn run_frames(&mut self, n: u32) -> RunOutcome {
+        let turbo = mem::replace(&mut self.turbo, true);
+        let mut outcome = RunOutcome::Completed;
+        for _ in 0..n {
+            outcome = self.tick_frame();
+            if outcome != RunOutcome::Completed {
+                break;
+            }
+        }
+        self.turbo = turbo;
+        outcome
+    }
+
+    /// Runs frames until the program stops, sleeping between frames unless in turbo mode.
+    pub fn run(&mut self) -> RunOutcome {
+        self.run_until(None)
+    }
+
+    /// Like `run`, but gives up with `RunOutcome::Completed` once `duration` of wall-clock
+    /// time has passed, so a hanging ROM can't stall a test or sandbox forever.
+    pub fn run_for(&mut self, duration: Duration) -> RunOutcome {
+        self.run_until(Some(Instant::now() + duration))
+    }
+
+    fn run_until(&mut self, deadline: Option<Instant>) -> RunOutcome {
+        let frame = self.frame_duration();
+        while deadline.is_none_or(|deadline| Instant::now() < deadline) {
+            let frame_start = Instant::now();
+            let outcome = self.tick_frame();
+            if outcome != RunOutcome::Completed {
+                return outcome;
+            }
+            if !self.turbo {
+                let mut pause = frame.saturating_sub(frame_start.elapsed());
+                if let Some(deadline) = deadline {
+                    pause = pause.min(deadline.saturating_duration_since(Instant::now()));
+                }
+                thread::sleep(pause);
+            }
+        }
+        RunOutcome::Completed
+    }
+
+    /// Runs an interpreter on a new thread, built there by `make`. The interpreter is `Send`,
+    /// as its clock, beeper and hooks all have to be, but not `Sync`, so it's only ever touched
+    /// on that thread; key events go in and screen snapshots come out through the channels in
+    /// the handle.
+    /// The thread stops when the program does or when the handle is stopped or dropped.
+    pub fn spawn(make: impl FnOnce() -> Interpreter + Send + 'static) -> InterpreterHandle {
+        let (keys, key_receiver) = mpsc::channel();
+        let (frame_sender, frames) = mpsc::sync_channel(FRAME_QUEUE_SIZE);
+        let thread = thread::spawn(move || make().run_threaded(key_receiver, frame_sender));
+        InterpreterHandle {
+            keys,
+            frames,
+            thread,
+        }
+    }
+
+    fn run_threaded(
+        &mut self,
+        keys: Receiver<KeyEvent>,
+        frames: SyncSender<Vec<u8>>,
+    ) -> RunOutcome {
+        let frame = self.frame_duration();
+        loop {
+            let frame_start = Instant::now();
+            loop {
+                match keys.try_recv() {
+                    Ok(event) => self.push_key_event(event),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return RunOutcome::Completed,
+                }
+            }
+            let outcome = self.tick_frame();
+            if let Err(TrySendError::Disconnected(_)) = frames.try_send(self.snapshot_screen()) {
+                return RunOutcome::Completed;
+            }
+            if outcome != RunOutcome::Completed {
+                return outcome;
+            }
+            if !self.turbo {
+                thread::sleep(frame.saturating_sub(frame_start.elapsed()));
+            }
+        }
+    }
+
+    fn execute(&mut self, instruction: Instruction) -> Result<(), Fault> {
+        if matches!(
+            instruction,
+            Instruction::DrawSprite(..)
+                | Instruction::BinaryCodedDecimal(_)
+                | Instruction::StoreRegisters(_)
+                | Instruction::LoadRegisters(_)
+                | Instruction::LoadAudioPattern
+        ) {
+            self.check_unset_i();
+        }
+        match instruction {
+            Instruction::Unknown(_) => {}
+            Instruction::DrawLargeSprite(..)
+            | Instruction::LargeFontCharacter(_)
+            | Instruction::StoreFlags(_)
+            | Instruction::LoadFlags(_) => {
+                return Err(Fault::Unsupported {
+                    pc: self.instruction_address(),
+                    instruction,
+                })
+            }
+            Instruction::ScrollDown(rows) => self.scroll(0, rows as isize),
+            Instruction::ScrollRight => self.scroll(4, 0),
+            Instruction::ScrollLeft => self.scroll(-4, 0),
+            Instruction::LowRes => self.set_hires(false),
+            Instruction::HighRes => self.set_hires(true),
+            // Stay on the exit so the program can't be resumed past it.
+            Instruction::Exit => self.pc = self.instruction_address(),
+            Instruction::ClearScreen => {
+                self.check_flicker();
+                self.screen_changed = true;
+                for pixel in self.screen_buffer.iter_mut() {
+                    *pixel &= !self.planes;
+                }
+            }
+            Instruction::Return => {
+                self.check_stack_balance(-1);
+                self.pc = self.stack.pop().ok_or(Fault::StackUnderflow)?
+            }
+            Instruction::Jump(address) => {
+                self.check_jump_target(address)?;
+                self.pc = address
+            }
+            Instruction::JumpWithOffset(address) => {
+                let x = if self.config.quirks.jump_uses_vx {
+                    address >> 8
+                } else {
+                    0
+                };
+                let target = address + self.registers[x] as usize;
+                self.check_jump_target(target)?;
+                self.pc = target
+            }
+            Instruction::Call(address) => {
+                self.check_jump_target(address)?;
+                self.check_stack_balance(1);
+                self.stack.push(self.pc)?;
+                self.pc = address;
+            }
+            Instruction::SkipIfEqual(x, value) => {
+                if self.registers[x] == value {
+                    self.pc += 2;
+                }
+            }
+            Instruction::SkipIfNotEqual(x, value) => {
+                if self.registers[x] != value {
+                    self.pc += 2;
+                }
+            }
+            Instruction::SetRegister(x, value) => self.registers[x] = value,
+            Instruction::AddToRegister(x, value) => {
+                self.registers[x] = self.registers[x].wrapping_add(value)
+            }
+            // The flag is written last, so VF as a source is read before it changes, and VF as
+            // the destination ends up holding the flag rather than the result.
+            Instruction::Subtract(x, y) => self.subtract(x, self.registers[x], self.registers[y]),
+            Instruction::SubtractReversed(x, y) => {
+                self.subtract(x, self.registers[y], self.registers[x])
+            }
+            Instruction::ShiftRight(x, y) => {
+                let value = self.shift_source(x, y);
+                self.registers[x] = value >> 1;
+                self.registers[0xF] = value & 1;
+            }
+            Instruction::ShiftLeft(x, y) => {
+                let value = self.shift_source(x, y);
+                self.registers[x] = value << 1;
+                self.registers[0xF] = value >> 7;
+            }
+            Instruction::SetI(value) => {
+                self.i = value as usize;
+                self.i_set = true;
+            }
+            Instruction::Random(x, mask) => self.registers[x] = self.rng.next_u8() & mask,
+            Instruction::DrawSprite(x, y, height) => {
+                self.last_draw =
+                    Some((self.instruction_address(), self.cycles, self.timers.frames));
+                self.screen_changed = true;
+                self.draw_sprite(x, y, height)?
+            }
+            Instruction::SelectPlanes(planes) => self.planes = planes,
+            Instruction::LoadAudioPattern => {
+                let mut pattern = [0; 16];
+                for (offset, byte) in pattern.iter_mut().enumerate() {
+                    *byte = self.read_memory(self.i_address(offset))?;
+                }
+                self.audio_pattern = pattern;
+            }
+            Instruction::SetPitch(x) => self.pitch = self.registers[x],
+            Instruction::GetDelayTimer(x) => self.registers[x] = self.timers.delay_timer,
+            Instruction::SkipIfKey(x) => {
+                if self.key_held((self.registers[x] & 0x0F) as usize) {
+                    self.pc += 2;
+                }
+            }
+            Instruction::SkipIfNotKey(x) => {
+                if !self.key_held((self.registers[x] & 0x0F) as usize) {
+                    self.pc += 2;
+                }
+            }
+            Instruction::SetDelayTimer(x) => self.timers.delay_timer = self.registers[x],
+            Instruction::SetSoundTimer(x) => {
+                self.timers.sound_timer = self.registers[x];
+                self.update_beeper();
+            }
+            Instruction::WaitForKey(x) => {
+                if self.just_pressed == 0 {
+                    // Blocks by running this instruction again, so frames and with them the
+                    // timers carry on like on the VIP, and a beep started before keeps sounding.
+                    self.pc = self.instruction_address();
+                } else {
+                    let key = self.just_pressed.trailing_zeros();
+                    self.just_pressed &= !(1 << key);
+                    self.registers[x] = key as u8;
+                }
+            }
+            Instruction::AddToI(x) => {
+                self.i = (self.i + self.registers[x] as usize) & I_MASK;
+                self.i_set = true;
+            }
+            Instruction::FontCharacter(x) => {
+                self.i = self.config.font_address + (self.registers[x] & 0x0F) as usize * 5;
+                self.i_set = true;
+            }
+            Instruction::BinaryCodedDecimal(x) => {
+                let value = self.registers[x];
+                for (offset, digit) in [value / 100, value / 10 % 10, value % 10]
+                    .into_iter()
+                    .enumerate()
+                {
+                    self.write_memory(self.i_address(offset), digit)?;
+                }
+            }
+            Instruction::StoreRegisters(x) => {
+                for offset in 0..=x {
+                    self.write_memory(self.i_address(offset), self.registers[offset])?;
+                }
+                if self.config.quirks.memory_increments_i {
+                    self.i = (self.i + x + 1) & I_MASK;
+                }
+            }
+            Instruction::LoadRegisters(x) => {
+                let mut data = [0; 16];
+                for (offset, byte) in data[..=x].iter_mut().enumerate() {
+                    *byte = self.read_memory(self.i_address(offset))?;
+                }
+                self.registers_from_slice(&data[..=x])?;
+                if self.config.quirks.memory_increments_i {
+                    self.i = (self.i + x + 1) & I_MASK;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_jump_target(&self, target: usize) -> Result<(), Fault> {
+        if self.config.protect_reserved_jumps && target < PC_START_ADDRESS {
+            return Err(Fault::JumpToReserved {
+                pc: self.instruction_address(),
+                target,
+            });
+        }
+        Ok(())
+    }
+
+    /// Records a `Diagnostic::UnsetI` if an access through `i` looks like it forgot to set it.
+    fn check_unset_i(&mut self) {
+        if self.config.check_unset_i && !self.i_set && self.i < PC_START_ADDRESS {
+            self.report(Diagnostic::UnsetI {
+                pc: self.instruction_address(),
+            });
+        }
+    }
+
+    /// Stores `minuend - subtrahend` in `Vx`, with VF set to 1 when there was no borrow.
+    fn subtract(&mut self, x: usize, minuend: u8, subtrahend: u8) {
+        let (difference, borrow) = minuend.overflowing_sub(subtrahend);
+        self.registers[x] = difference;
+        self.registers[0xF] = !borrow as u8;
+    }
+
+    /// The register `8XY6`/`8XYE` shift, following the `shift_uses_vy` quirk.
+    fn shift_source(&self, x: usize, y: usize) -> u8 {
+        if self.config.quirks.shift_uses_vy {
+            self.registers[y]
+        } else {
+            self.registers[x]
+        }
+    }
+
+    /// Records a `Diagnostic::DrawThenClear` if the screen is cleared too soon after a draw.
+    fn check_flicker(&mut self) {
+        let (Some(window), Some((draw_pc, draw_cycle, draw_frame))) =
+            (self.config.flicker_window, self.last_draw)
+        else {
+            return;
+        };
+        if self.cycles - draw_cycle <= window && self.timers.frames == draw_frame {
+            self.report(Diagnostic::DrawThenClear {
+                draw_pc,
+                clear_pc: self.instruction_address(),
+            });
+        }
+    }
+
+    /// Adds a call or return to the stack imbalance window and records a
+    /// `Diagnostic::StackImbalance` when it tips the balance over the threshold.
+    fn check_stack_balance(&mut self, change: isize) {
+        let Some((window, threshold)) = self.config.stack_imbalance else {
+            return;
+        };
+        let (start, delta) = &mut self.stack_window;
+        if self.cycles - *start > window {
+            *start = self.cycles;
+            *delta = 0;
+        }
+        *delta += change;
+        let delta = *delta;
+        if delta.unsigned_abs() == threshold + 1 {
+            self.report(Diagnostic::StackImbalance {
+                pc: self.instruction_address(),
+                delta,
+            });
+        }
+    }
+
+    /// Records a diagnostic, once per distinct finding.
+    fn report(&mut self, diagnostic: Diagnostic) {
+        if !self.diagnostics.contains(&diagnostic) {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    fn set_hires(&mut self, hires: bool) {
+        let size = self.display_size();
+        self.hires = hires;
+        // The old contents would come out garbled at the new size.
+        if self.display_size() != size {
+            self.screen_buffer.fill(PIXEL_OFF);
+            self.fade_buffer.fill(0);
+            self.screen_changed = true;
+        }
+    }
+
+    /// Moves the selected planes by whole display pixels, so lo-res scrolls on a scaled display
+    /// move by half a lo-res pixel, as on SUPER-CHIP.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = self.display_size();
+        let (width, height) = (width as isize, height as isize);
+        let wraps = self.config.quirks.scroll_wraps;
+        let before = self.screen_buffer;
+        for y in 0..height {
+            for x in 0..width {
+                let (from_x, from_y) = (x - dx, y - dy);
+                let inside = (0..width).contains(&from_x) && (0..height).contains(&from_y);
+                let moved = if inside || wraps {
+                    let from = from_y.rem_euclid(height) * width + from_x.rem_euclid(width);
+                    before[from as usize] & self.planes
+                } else {
+                    0
+                };
+                let pixel = &mut self.screen_buffer[(y * width + x) as usize];
+                *pixel = (*pixel & !self.planes) | moved;
+            }
+        }
+        self.screen_changed = true;
+    }
+
+    fn draw_sprite(&mut self, x: usize, y: usize, height: u8) -> Result<(), Fault> {
+        let (display_width, display_height) = self.display_size();
+        // Lo-res pixels become 2x2 blocks on a display that stays hi-res.
+        let scale = if self.hires {
+            1
+        } else {
+            display_width / SCREEN_WIDTH
+        };
+        let (screen_width, screen_height) = (display_width / scale, display_height / scale);
+
+        // The start position always wraps, clipping only ever applies to the rest of the sprite.
+        let start_x = self.registers[x] as usize % screen_width;
+        let start_y = self.registers[y] as usize % screen_height;
+        let Quirks { clip_x, clip_y, .. } = self.config.quirks;
+        self.registers[0xF] = 0;
+        self.collision_mask.fill(false);
+
+        let width = if clip_x {
+            8.min(screen_width - start_x)
+        } else {
+            8
+        };
+        let rows = if clip_y {
+            (height as usize).min(screen_height - start_y)
+        } else {
+            height as usize
+        };
+        self.last_draw_bounds = Some((
+            start_x * scale,
+            start_y * scale,
+            width * scale,
+            rows * scale,
+        ));
+
+        // With several planes selected, the sprite data for each plane follows the previous one.
+        let mut offset = 0;
+        // Display rows the sprite covered, one bit per row.
+        let mut touched: u64 = 0;
+        for plane in 0..PLANE_COUNT {
+            let plane_bit = 1 << plane;
+            if self.planes & plane_bit == 0 {
+                continue;
+            }
+
+            for row in 0..height as usize {
+                let pixel_y = start_y + row;
+                if clip_y && pixel_y >= screen_height {
+                    break;
+                }
+                let pixel_y = pixel_y % screen_height;
+                for dy in 0..scale {
+                    touched |= 1 << (pixel_y * scale + dy);
+                }
+
+                let sprite = self.read_memory(self.i_address(offset + row))?;
+                for column in 0..8 {
+                    let pixel_x = start_x + column;
+                    if clip_x && pixel_x >= screen_width {
+                        break;
+                    }
+                    let pixel_x = pixel_x % screen_width;
+                    if sprite & (0x80 >> column) == 0 {
+                        continue;
+                    }
+
+                    for (dx, dy) in (0..scale).flat_map(|dy| (0..scale).map(move |dx| (dx, dy))) {
+                        let index = (pixel_y * scale + dy) * display_width + pixel_x * scale + dx;
+                        let pixel = &mut self.screen_buffer[index];
+                        if *pixel & plane_bit != 0 {
+                            self.registers[0xF] = 1;
+                            self.collision_mask[index] = true;
+                        }
+                        *pixel ^= plane_bit;
+                    }
+                }
+            }
+            offset += height as usize;
+        }
+
+        if let Some(mut on_scanline) = self.on_scanline.take() {
+            for row in (0..display_height).filter(|row| touched & (1 << row) != 0) {
+                let pixels = &self.screen_buffer[row * display_width..(row + 1) * display_width];
+                let bits = pixels
+                    .iter()
+                    .enumerate()
+                    .fold(0, |bits, (x, pixel)| bits | ((*pixel != 0) as u128) << x);
+                on_scanline(row, bits);
+            }
+            self.on_scanline = Some(on_scanline);
+        }
+        Ok(())
+    }
+}
+
+/// Sums up the CPU state. Memory, the screen and the hooks are left out.
+impl fmt::Debug for Interpreter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Interpreter")
+            .field("pc", &format_args!("{:#05X}", self.pc))
+            .field("i", &format_args!("{:#05X}", self.i))
+            .field("registers", &format_args!("{:02X?}", self.registers))
+            .field("stack_depth", &self.stack.position)
+            .field("delay_timer", &self.timers.delay_timer)
+            .field("sound_timer", &self.timers.sound_timer)
+            .field("cycles", &self.cycles)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Decoders for each high nibble, see `Instruction::from_raw_table`.
+const DECODE_TABLE: [fn(u16) -> Instruction; 16] = [
+    Instruction::decode_system,
+    |bytes| Instruction::Jump((bytes & 0x0FFF) as usize),
+    |bytes| Instruction::Call((bytes & 0x0FFF) as usize),
+    |bytes| Instruction::SkipIfEqual(Instruction::nibble_left(bytes, 1) as usize, bytes as u8),
+    |bytes| Instruction::SkipIfNotEqual(Instruction::nibble_left(bytes, 1) as usize, bytes as u8),
+    Instruction::Unknown,
+    |bytes| Instruction::SetRegister(Instruction::nibble_left(bytes, 1) as usize, bytes as u8),
+    |bytes| Instruction::AddToRegister(Instruction::nibble_left(bytes, 1) as usize, bytes as u8),
+    Instruction::decode_arithmetic,
+    Instruction::Unknown,
+    |bytes| Instruction::SetI(bytes & 0x0FFF),
+    |bytes| Instruction::JumpWithOffset((bytes & 0x0FFF) as usize),
+    |bytes| Instruction::Random(Instruction::nibble_left(bytes, 1) as usize, bytes as u8),
+    Instruction::decode_draw,
+    Instruction::decode_key,
+    Instruction::decode_misc,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Instruction {
+    /// A word that doesn't decode to any instruction, most likely data.
+    Unknown(u16),
+    ScrollDown(u8),
+    ClearScreen,
+    Return,
+    ScrollRight,
+    ScrollLeft,
+    Exit,
+    LowRes,
+    HighRes,
+    Jump(usize),
+    /// `BNNN`, see `Quirks::jump_uses_vx` for the register added.
+    JumpWithOffset(usize),
+    Call(usize),
+    SkipIfEqual(usize, u8),
+    SkipIfNotEqual(usize, u8),
+    SetRegister(usize, u8),
+    AddToRegister(usize, u8),
+    Subtract(usize, usize),
+    ShiftRight(usize, usize),
+    SubtractReversed(usize, usize),
+    ShiftLeft(usize, usize),
+    SetI(u16),
+    Random(usize, u8),
+    DrawSprite(usize, usize, u8),
+    /// `DXY0`, a 16x16 sprite on SUPER-CHIP.
+    DrawLargeSprite(usize, usize),
+    SkipIfKey(usize),
+    SkipIfNotKey(usize),
+    SelectPlanes(u8),
+    LoadAudioPattern,
+    SetPitch(usize),
+    GetDelayTimer(usize),
+    WaitForKey(usize),
+    SetDelayTimer(usize),
+    SetSoundTimer(usize),
+    /// `FX1E`, adds `VX` to `i` without touching `VF`.
+    AddToI(usize),
+    FontCharacter(usize),
+    /// `FX30`, points `i` at the SUPER-CHIP 8x10 digit for `VX`.
+    LargeFontCharacter(usize),
+    BinaryCodedDecimal(usize),
+    StoreRegisters(usize),
+    LoadRegisters(usize),
+    /// `FX75`, saves V0 up to `VX` to the SUPER-CHIP flag registers.
+    StoreFlags(usize),
+    /// `FX85`, restores V0 up to `VX` from the SUPER-CHIP flag registers.
+    LoadFlags(usize),
+}
+
+impl Instruction {
+    fn from_raw(bytes: u16) -> Self {
+        match Self::nibble_left(bytes, 0) {
+            0 => Self::decode_system(bytes),
+            1 => Self::Jump((bytes & 0x0FFF) as usize),
+            2 => Self::Call((bytes & 0x0FFF) as usize),
+            3 => Self::SkipIfEqual(Self::nibble_left(bytes, 1) as usize, bytes as u8),
+            4 => Self::SkipIfNotEqual(Self::nibble_left(bytes, 1) as usize, bytes as u8),
+            6 => Self::SetRegister(Self::nibble_left(bytes, 1) as usize, bytes as u8),
+            7 => Self::AddToRegister(Self::nibble_left(bytes, 1) as usize, bytes as u8),
+            8 => Self::decode_arithmetic(bytes),
+            0xA => Self::SetI(bytes & 0x0FFF),
+            0xB => Self::JumpWithOffset((bytes & 0x0FFF) as usize),
+            0xC => Self::Random(Self::nibble_left(bytes, 1) as usize, bytes as u8),
+            0xD => Self::decode_draw(bytes),
+            0xE => Self::decode_key(bytes),
+            0xF => Self::decode_misc(bytes),
+            _ => Self::Unknown(bytes),
+        }
+    }
+
+    /// The same decoding as `from_raw`, dispatched through `DECODE_TABLE` instead of a match.
+    /// There to compare dispatch strategies. On x86-64 `decode_table_benchmark` (behind the
+    /// `bench` feature) measured the table at about three times slower, as the match compiles
+    /// to a jump table already and can inline the sub-decoders, so `from_raw` stays in use.
+    fn from_raw_table(bytes: u16) -> Self {
+        DECODE_TABLE[Self::nibble_left(bytes, 0) as usize](bytes)
+    }
+
+    fn decode_system(bytes: u16) -> Self {
+        // 0NNN machine code routines aren't supported, only the 00xx instructions.
+        if bytes & 0x0F00 != 0 {
+            return Self::Unknown(bytes);
+        }
+        match bytes as u8 {
+            0xC0..=0xCF => Self::ScrollDown(Self::nibble_right(bytes, 0)),
+            0xE0 => Self::ClearScreen,
+            0xEE => Self::Return,
+            0xFB => Self::ScrollRight,
+            0xFC => Self::ScrollLeft,
+            0xFD => Self::Exit,
+            0xFE => Self::LowRes,
+            0xFF => Self::HighRes,
+            _ => Self::Unknown(bytes),
+        }
+    }
+
+    fn decode_arithmetic(bytes: u16) -> Self {
+        let (x, y) = (
+            Self::nibble_left(bytes, 1) as usize,
+            Self::nibble_left(bytes, 2) as usize,
+        );
+        match Self::nibble_right(bytes, 0) {
+            0x5 => Self::Subtract(x, y),
+            0x6 => Self::ShiftRight(x, y),
+            0x7 => Self::SubtractReversed(x, y),
+            0xE => Self::ShiftLeft(x, y),
+            _ => Self::Unknown(bytes),
+        }
+    }
+
+    fn decode_draw(bytes: u16) -> Self {
+        let (x, y) = (
+            Self::nibble_left(bytes, 1) as usize,
+            Self::nibble_left(bytes, 2) as usize,
+        );
+        match Self::nibble_right(bytes, 0) {
+            0 => Self::DrawLargeSprite(x, y),
+            height => Self::DrawSprite(x, y, height),
+        }
+    }
+
+    fn decode_key(bytes: u16) -> Self {
+        match bytes & 0x00FF {
+            0x9E => Self::SkipIfKey(Self::nibble_left(bytes, 1) as usize),
+            0xA1 => Self::SkipIfNotKey(Self::nibble_left(bytes, 1) as usize),
+            _ => Self::Unknown(bytes),
+        }
+    }
+
+    fn decode_misc(bytes: u16) -> Self {
+        match bytes & 0x00FF {
+            0x01 => Self::SelectPlanes(Self::nibble_left(bytes, 1)),
+            0x02 if bytes & 0x0F00 == 0 => Self::LoadAudioPattern,
+            0x07 => Self::GetDelayTimer(Self::nibble_left(bytes, 1) as usize),
+            0x0A => Self::WaitForKey(Self::nibble_left(bytes, 1) as usize),
+            0x15 => Self::SetDelayTimer(Self::nibble_left(bytes, 1) as usize),
+            0x18 => Self::SetSoundTimer(Self::nibble_left(bytes, 1) as usize),
+            0x1E => Self::AddToI(Self::nibble_left(bytes, 1) as usize),
+            0x29 => Self::FontCharacter(Self::nibble_left(bytes, 1) as usize),
+            0x30 => Self::LargeFontCharacter(Self::nibble_left(bytes, 1) as usize),
+            0x33 => Self::BinaryCodedDecimal(Self::nibble_left(bytes, 1) as usize),
+            0x3A => Self::SetPitch(Self::nibble_left(bytes, 1) as usize),
+            0x55 => Self::StoreRegisters(Self::nibble_left(bytes, 1) as usize),
+            0x65 => Self::LoadRegisters(Self::nibble_left(bytes, 1) as usize),
+            0x75 => Self::StoreFlags(Self::nibble_left(bytes, 1) as usize),
+            0x85 => Self::LoadFlags(Self::nibble_left(bytes, 1) as usize),
+            _ => Self::Unknown(bytes),
+        }
+    }
+
+    /// Approximate COSMAC VIP machine cycles spent on the instruction, not counting the wait for
+    /// the display. Instructions the VIP didn't have are given the cost of their closest relative.
+    pub fn cycle_cost(&self) -> u32 {
+        match *self {
+            Self::Unknown(_) => 0,
+            Self::ClearScreen => 24,
+            Self::Return => 10,
+            Self::Jump(_) | Self::SetI(_) => 12,
+            Self::JumpWithOffset(_) => 22,
+            Self::Call(_) => 26,
+            Self::SkipIfEqual(..) | Self::SkipIfNotEqual(..) => 10,
+            Self::SkipIfKey(_) | Self::SkipIfNotKey(_) => 14,
+            Self::SetRegister(..) => 6,
+            Self::AddToRegister(..) => 10,
+            Self::Subtract(..)
+            | Self::SubtractReversed(..)
+            | Self::ShiftRight(..)
+            | Self::ShiftLeft(..) => 44,
+            Self::Random(..) => 36,
+            Self::DrawSprite(_, _, height) => 22 + 68 * height as u32,
+            Self::DrawLargeSprite(..) => 22 + 68 * 32,
+            Self::GetDelayTimer(_) | Self::SetDelayTimer(_) | Self::SetSoundTimer(_) => 10,
+            Self::WaitForKey(_) => 8,
+            Self::AddToI(_) => 12,
+            Self::FontCharacter(_) | Self::LargeFontCharacter(_) => 20,
+            Self::BinaryCodedDecimal(_) => 84,
+            Self::StoreRegisters(x)
+            | Self::LoadRegisters(x)
+            | Self::StoreFlags(x)
+            | Self::LoadFlags(x) => 14 + 14 * (x as u32 + 1),
+            Self::LoadAudioPattern => 14 + 14 * 16,
+            Self::SetPitch(_) => 10,
+            Self::ScrollDown(_)
+            | Self::ScrollRight
+            | Self::ScrollLeft
+            | Self::LowRes
+            | Self::HighRes
+            | Self::SelectPlanes(_) => 24,
+            Self::Exit => 0,
+        }
+    }
+
+    /// The `X` register operand, if the instruction has one.
+    pub fn vx(&self) -> Option<usize> {
+        match *self {
+            Self::SkipIfEqual(x, _)
+            | Self::SkipIfNotEqual(x, _)
+            | Self::SetRegister(x, _)
+            | Self::AddToRegister(x, _)
+            | Self::Subtract(x, _)
+            | Self::SubtractReversed(x, _)
+            | Self::ShiftRight(x, _)
+            | Self::ShiftLeft(x, _)
+            | Self::Random(x, _)
+            | Self::DrawSprite(x, _, _)
+            | Self::DrawLargeSprite(x, _)
+            | Self::SkipIfKey(x)
+            | Self::SkipIfNotKey(x)
+            | Self::GetDelayTimer(x)
+            | Self::WaitForKey(x)
+            | Self::SetDelayTimer(x)
+            | Self::SetSoundTimer(x)
+            | Self::AddToI(x)
+            | Self::FontCharacter(x)
+            | Self::LargeFontCharacter(x)
+            | Self::BinaryCodedDecimal(x)
+            | Self::SetPitch(x)
+            | Self::StoreRegisters(x)
+            | Self::LoadRegisters(x)
+            | Self::StoreFlags(x)
+            | Self::LoadFlags(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// The `Y` register operand, if the instruction has one.
+    pub fn vy(&self) -> Option<usize> {
+        match *self {
+            Self::Subtract(_, y)
+            | Self::SubtractReversed(_, y)
+            | Self::ShiftRight(_, y)
+            | Self::ShiftLeft(_, y)
+            | Self::DrawSprite(_, y, _)
+            | Self::DrawLargeSprite(_, y) => Some(y),
+            _ => None,
+        }
+    }
+
+    /// The 12-bit address operand, if the instruction has one.
+    pub fn nnn(&self) -> Option<usize> {
+        match *self {
+            Self::Jump(address) | Self::JumpWithOffset(address) | Self::Call(address) => {
+                Some(address)
+            }
+            Self::SetI(address) => Some(address as usize),
+            _ => None,
+        }
+    }
+
+    /// The 8-bit immediate operand, if the instruction has one.
+    pub fn nn(&self) -> Option<u8> {
+        match *self {
+            Self::SkipIfEqual(_, value)
+            | Self::SkipIfNotEqual(_, value)
+            | Self::SetRegister(_, value)
+            | Self::AddToRegister(_, value)
+            | Self::Random(_, value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The 4-bit immediate operand, if the instruction has one.
+    pub fn n(&self) -> Option<u8> {
+        match *self {
+            Self::ScrollDown(n) | Self::DrawSprite(_, _, n) => Some(n),
+            _ => None,
+        }
+    }
+
+    fn nibble_left(bytes: u16, position: usize) -> u8 {
+        assert!(position < 4);
+        let mask = 0xF000 >> (position * 4);
+        let shift = 12 - position * 4;
+        ((bytes & mask) >> shift) as u8
+    }
+
+    /// Like `nibble_left`, but counting from the low end, so position 0 is the `N` in `DXYN`.
+    fn nibble_right(bytes: u16, position: usize) -> u8 {
+        assert!(position < 4);
+        ((bytes >> (position * 4)) & 0x000F) as u8
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Unknown(bytes) => write!(f, "DB {bytes:#06X}"),
+            Self::ScrollDown(rows) => write!(f, "SCD {rows}"),
+            Self::ClearScreen => write!(f, "CLS"),
+            Self::Return => write!(f, "RET"),
+            Self::ScrollRight => write!(f, "SCR"),
+            Self::ScrollLeft => write!(f, "SCL"),
+            Self::Exit => write!(f, "EXIT"),
+            Self::LowRes => write!(f, "LOW"),
+            Self::HighRes => write!(f, "HIGH"),
+            Self::Jump(address) => write!(f, "JP {address:#05X}"),
+            Self::JumpWithOffset(address) => write!(f, "JP V0, {address:#05X}"),
+            Self::Call(address) => write!(f, "CALL {address:#05X}"),
+            Self::SkipIfEqual(x, value) => write!(f, "SE V{x:X}, {value:#04X}"),
+            Self::SkipIfNotEqual(x, value) => write!(f, "SNE V{x:X}, {value:#04X}"),
+            Self::SetRegister(x, value) => write!(f, "LD V{x:X}, {value:#04X}"),
+            Self::AddToRegister(x, value) => write!(f, "ADD V{x:X}, {value:#04X}"),
+            Self::Subtract(x, y) => write!(f, "SUB V{x:X}, V{y:X}"),
+            Self::ShiftRight(x, y) => write!(f, "SHR V{x:X}, V{y:X}"),
+            Self::SubtractReversed(x, y) => write!(f, "SUBN V{x:X}, V{y:X}"),
+            Self::ShiftLeft(x, y) => write!(f, "SHL V{x:X}, V{y:X}"),
+            Self::SetI(address) => write!(f, "LD I, {address:#05X}"),
+            Self::Random(x, mask) => write!(f, "RND V{x:X}, {mask:#04X}"),
+            Self::DrawSprite(x, y, height) => write!(f, "DRW V{x:X}, V{y:X}, {height}"),
+            Self::DrawLargeSprite(x, y) => write!(f, "DRW V{x:X}, V{y:X}, 0"),
+            Self::SkipIfKey(x) => write!(f, "SKP V{x:X}"),
+            Self::SkipIfNotKey(x) => write!(f, "SKNP V{x:X}"),
+            Self::SelectPlanes(planes) => write!(f, "PLANE {planes}"),
+            Self::LoadAudioPattern => write!(f, "AUDIO"),
+            Self::SetPitch(x) => write!(f, "PITCH V{x:X}"),
+            Self::GetDelayTimer(x) => write!(f, "LD V{x:X}, DT"),
+            Self::WaitForKey(x) => write!(f, "LD V{x:X}, K"),
+            Self::SetDelayTimer(x) => write!(f, "LD DT, V{x:X}"),
+            Self::SetSoundTimer(x) => write!(f, "LD ST, V{x:X}"),
+            Self::AddToI(x) => write!(f, "ADD I, V{x:X}"),
+            Self::FontCharacter(x) => write!(f, "LD F, V{x:X}"),
+            Self::LargeFontCharacter(x) => write!(f, "LD HF, V{x:X}"),
+            Self::BinaryCodedDecimal(x) => write!(f, "LD B, V{x:X}"),
+            Self::StoreRegisters(x) => write!(f, "LD [I], V{x:X}"),
+            Self::LoadRegisters(x) => write!(f, "LD V{x:X}, [I]"),
+            Self::StoreFlags(x) => write!(f, "LD R, V{x:X}"),
+            Self::LoadFlags(x) => write!(f, "LD V{x:X}, R"),
+        }
+    }
+}
+
+/// An opcode the interpreter implements, matching every word `w` with `w & mask == pattern`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct OpcodeInfo {
+    mnemonic: &'static str,
+    mask: u16,
+    pattern: u16,
+    description: &'static str,
+}
+
+impl OpcodeInfo {
+    pub fn matches(&self, opcode: u16) -> bool {
+        opcode & self.mask == self.pattern
+    }
+}
+
+/// Every opcode `Instruction::from_raw` decodes, in opcode order.
+const SUPPORTED_OPCODES: [OpcodeInfo; 39] = [
+    OpcodeInfo {
+        mnemonic: "SCD",
+        mask: 0xFFF0,
+        pattern: 0x00C0,
+        description: "Scroll the selected planes down N pixels",
+    },
+    OpcodeInfo {
+        mnemonic: "CLS",
+        mask: 0xFFFF,
+        pattern: 0x00E0,
+        description: "Clear the selected planes",
+    },
+    OpcodeInfo {
+        mnemonic: "RET",
+        mask: 0xFFFF,
+        pattern: 0x00EE,
+        description: "Return from a subroutine",
+    },
+    OpcodeInfo {
+        mnemonic: "SCR",
+        mask: 0xFFFF,
+        pattern: 0x00FB,
+        description: "Scroll the selected planes right 4 pixels",
+    },
+    OpcodeInfo {
+        mnemonic: "SCL",
+        mask: 0xFFFF,
+        pattern: 0x00FC,
+        description: "Scroll the selected planes left 4 pixels",
+    },
+    OpcodeInfo {
+        mnemonic: "EXIT",
+        mask: 0xFFFF,
+        pattern: 0x00FD,
+        description: "Stop the interpreter",
+    },
+    OpcodeInfo {
+        mnemonic: "LOW",
+        mask: 0xFFFF,
+        pattern: 0x00FE,
+        description: "Switch to 64x32 mode",
+    },
+    OpcodeInfo {
+        mnemonic: "HIGH",
+        mask: 0xFFFF,
+        pattern: 0x00FF,
+        description: "Switch to 128x64 mode",
+    },
+    OpcodeInfo {
+        mnemonic: "JP",
+        mask: 0xF000,
+        pattern: 0x1000,
+        description: "Jump to NNN",
+    },
+    OpcodeInfo {
+        mnemonic: "CALL",
+        mask: 0xF000,
+        pattern: 0x2000,
+        description: "Call the subroutine at NNN",
+    },
+    OpcodeInfo {
+        mnemonic: "SE",
+        mask: 0xF000,
+        pattern: 0x3000,
+        description: "Skip the next instruction if VX == NN",
+    },
+    OpcodeInfo {
+        mnemonic: "SNE",
+        mask: 0xF000,
+        pattern: 0x4000,
+        description: "Skip the next instruction if VX != NN",
+    },
+    OpcodeInfo {
+        mnemonic: "LD",
+        mask: 0xF000,
+        pattern: 0x6000,
+        description: "Set VX to NN",
+    },
+    OpcodeInfo {
+        mnemonic: "ADD",
+        mask: 0xF000,
+        pattern: 0x7000,
+        description: "Add NN to VX without touching VF",
+    },
+    OpcodeInfo {
+        mnemonic: "SUB",
+        mask: 0xF00F,
+        pattern: 0x8005,
+        description: "Set VX to VX - VY, VF = 1 if there was no borrow",
+    },
+    OpcodeInfo {
+        mnemonic: "SHR",
+        mask: 0xF00F,
+        pattern: 0x8006,
+        description: "Shift VY, or VX in place, right into VX, VF = bit shifted out",
+    },
+    OpcodeInfo {
+        mnemonic: "SUBN",
+        mask: 0xF00F,
+        pattern: 0x8007,
+        description: "Set VX to VY - VX, VF = 1 if there was no borrow",
+    },
+    OpcodeInfo {
+        mnemonic: "SHL",
+        mask: 0xF00F,
+        pattern: 0x800E,
+        description: "Shift VY, or VX in place, left into VX, VF = bit shifted out",
+    },
+    OpcodeInfo {
+        mnemonic: "LD",
+        mask: 0xF000,
+        pattern: 0xA000,
+        description: "Set I to NNN",
+    },
+    OpcodeInfo {
+        mnemonic: "JP V0",
+        mask: 0xF000,
+        pattern: 0xB000,
+        description: "Jump to NNN plus V0, or plus VX on SUPER-CHIP",
+    },
+    OpcodeInfo {
+        mnemonic: "RND",
+        mask: 0xF000,
+        pattern: 0xC000,
+        description: "Set VX to a random byte masked with NN",
+    },
+    OpcodeInfo {
+        mnemonic: "DRW",
+        mask: 0xF000,
+        pattern: 0xD000,
+        description: "Draw an N rows tall sprite from I at VX, VY, VF = collision. N = 0 is a \
+                      SUPER-CHIP 16x16 sprite, which only decodes for now",
+    },
+    OpcodeInfo {
+        mnemonic: "SKP",
+        mask: 0xF0FF,
+        pattern: 0xE09E,
+        description: "Skip the next instruction if the key in VX is held",
+    },
+    OpcodeInfo {
+        mnemonic: "SKNP",
+        mask: 0xF0FF,
+        pattern: 0xE0A1,
+        description: "Skip the next instruction if the key in VX isn't held",
+    },
+    OpcodeInfo {
+        mnemonic: "PLANE",
+        mask: 0xF0FF,
+        pattern: 0xF001,
+        description: "Select the planes N to draw and clear",
+    },
+    OpcodeInfo {
+        mnemonic: "AUDIO",
+        mask: 0xFFFF,
+        pattern: 0xF002,
+        description: "Load the 16 byte audio pattern from I",
+    },
+    OpcodeInfo {
+        mnemonic: "LD",
+        mask: 0xF0FF,
+        pattern: 0xF007,
+        description: "Set VX to the delay timer",
+    },
+    OpcodeInfo {
+        mnemonic: "LD",
+        mask: 0xF0FF,
+        pattern: 0xF00A,
+        description: "Wait for a key press and store the key in VX",
+    },
+    OpcodeInfo {
+        mnemonic: "LD",
+        mask: 0xF0FF,
+        pattern: 0xF015,
+        description: "Set the delay timer to VX",
+    },
+    OpcodeInfo {
+        mnemonic: "LD",
+        mask: 0xF0FF,
+        pattern: 0xF018,
+        description: "Set the sound timer to VX",
+    },
+    OpcodeInfo {
+        mnemonic: "ADD",
+        mask: 0xF0FF,
+        pattern: 0xF01E,
+        description: "Add VX to I",
+    },
+    OpcodeInfo {
+        mnemonic: "LD",
+        mask: 0xF0FF,
+        pattern: 0xF029,
+        description: "Point I at the font glyph for the low nibble of VX",
+    },
+    OpcodeInfo {
+        mnemonic: "LD",
+        mask: 0xF0FF,
+        pattern: 0xF030,
+        description: "Point I at the large font digit for VX, only decodes for now",
+    },
+    OpcodeInfo {
+        mnemonic: "LD",
+        mask: 0xF0FF,
+        pattern: 0xF033,
+        description: "Store the decimal digits of VX at I",
+    },
+    OpcodeInfo {
+        mnemonic: "PITCH",
+        mask: 0xF0FF,
+        pattern: 0xF03A,
+        description: "Set the audio pattern pitch to VX",
+    },
+    OpcodeInfo {
+        mnemonic: "LD",
+        mask: 0xF0FF,
+        pattern: 0xF055,
+        description: "Store V0 to VX at I",
+    },
+    OpcodeInfo {
+        mnemonic: "LD",
+        mask: 0xF0FF,
+        pattern: 0xF065,
+        description: "Load V0 to VX from I",
+    },
+    OpcodeInfo {
+        mnemonic: "LD",
+        mask: 0xF0FF,
+        pattern: 0xF075,
+        description: "Save V0 to VX to the flag registers, only decodes for now",
+    },
+    OpcodeInfo {
+        mnemonic: "LD",
+        mask: 0xF0FF,
+        pattern: 0xF085,
+        description: "Restore V0 to VX from the flag registers, only decodes for now",
+    },
+];
+
+/// Turns program bytes into an address-prefixed listing.
+struct Disassembler {
+    labels: HashMap<usize, String>,
+}
+
+impl Disassembler {
+    /// Starts out with labels for the program entry point and every font glyph.
+    pub fn new() -> Self {
+        let mut labels = HashMap::new();
+        labels.insert(PC_START_ADDRESS, "entry".to_string());
+        for glyph in 0..16 {
+            labels.insert(FONT_START_ADDRESS + glyph * 5, format!("font_{glyph:X}"));
+        }
+        Self { labels }
+    }
+
+    pub fn add_label(&mut self, address: usize, label: &str) {
+        self.labels.insert(address, label.to_string());
+    }
+
+    /// One line per instruction, loaded at `PC_START_ADDRESS`. Labeled addresses get a
+    /// `label:` line of their own, and instructions referring to one are annotated with it.
+    pub fn disassemble(&self, bytes: &[u8]) -> String {
+        let mut listing = Vec::new();
+        self.write_disassembly(bytes, &mut listing)
+            .expect("writing to a Vec can't fail");
+        String::from_utf8(listing).expect("the listing is ASCII")
+    }
+
+    /// Like `disassemble`, but writes the listing to `out` line by line instead of building
+    /// it up in memory.
+    pub fn write_disassembly(&self, bytes: &[u8], out: &mut impl Write) -> io::Result<()> {
+        for (index, word) in bytes.chunks(2).enumerate() {
+            let address = PC_START_ADDRESS + index * 2;
+            if let Some(label) = self.labels.get(&address) {
+                writeln!(out, "{label}:")?;
+            }
+
+            let opcode = ((word[0] as u16) << 8) | *word.get(1).unwrap_or(&0) as u16;
+            let instruction = Instruction::from_raw(opcode);
+            write!(out, "{address:#05X}  {opcode:04X}  {instruction}")?;
+            if let Some(label) = instruction
+                .nnn()
+                .and_then(|target| self.labels.get(&target))
+            {
+                write!(out, "  ; {label}")?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+}
+
+struct Stack {
+    data: [usize; STACK_SIZE],
+    position: usize,
+    depth: usize,
+}
+
+impl Stack {
+    pub fn new() -> Self {
+        Self::with_depth(STACK_SIZE)
+    }
+
+    pub fn with_depth(depth: usize) -> Self {
+        assert!(
+            (1..=STACK_SIZE).contains(&depth),
+            "stack depth must be between 1 and {STACK_SIZE}"
+        );
+        Self {
+            data: [0; STACK_SIZE],
+            position: 0,
+            depth,
+        }
+    }
+
+    pub fn push(&mut self, address: usize) -> Result<(), Fault> {
+        if self.position >= self.depth {
+            return Err(Fault::StackOverflow);
+        }
+
+        self.data[self.position] = address;
+        self.position += 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<usize> {
+        if self.position == 0 {
+            return None;
+        }
+
+        self.position -= 1;
+        Some(self.data[self.position])
+    }
+}
+
+struct Timers {
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    /// Number of full frames that have passed, `frequency` per second.
+    pub frames: u64,
+    clock: Box<dyn Clock>,
+    last_update: Instant,
+    accumulator: TimerAccumulator,
+    /// Decrements per second.
+    frequency: u32,
+    rounding_remainder: f32,
+    /// Leftover time in nanoseconds times `frequency`, so it is always less than a second.
+    remainder_nanos: u64,
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        Self::with_clock(Box::new(SystemClock))
+    }
+
+    pub fn with_clock(clock: Box<dyn Clock>) -> Self {
+        Self {
+            delay_timer: 0,
+            sound_timer: 0,
+            frames: 0,
+            last_update: clock.now(),
+            clock,
+            accumulator: TimerAccumulator::default(),
+            frequency: TIMER_DECREMENT_FREQUENCY,
+            rounding_remainder: 0.0,
+            remainder_nanos: 0,
+        }
+    }
+
+    /// Decrements by the time passed since the last update. Returns how many frames passed.
+    pub fn decrement_timers(&mut self) -> u64 {
+        let now = self.clock.now();
+        let frames = self.advance(now - self.last_update);
+        self.last_update = now;
+        frames
+    }
+
+    /// Advances the timers by `delta` of emulated time, regardless of how much time actually passed.
+    pub fn advance_emulated(&mut self, delta: Duration) -> u64 {
+        let frames = self.advance(delta);
+        self.last_update = self.clock.now();
+        frames
+    }
+
+    fn advance(&mut self, delta: Duration) -> u64 {
+        // The remainder carries over even while both timers are idle, so frames keep a steady
+        // cadence for the frame counter.
+        let frames = match self.accumulator {
+            TimerAccumulator::Float => {
+                let amount = self.frequency as f32 * delta.as_secs_f32() + self.rounding_remainder;
+                self.rounding_remainder = amount - amount.floor();
+                amount.floor() as u64
+            }
+            TimerAccumulator::Nanos => {
+                const NANOS_PER_SECOND: u128 = 1_000_000_000;
+                let amount =
+                    delta.as_nanos() * self.frequency as u128 + self.remainder_nanos as u128;
+                self.remainder_nanos = (amount % NANOS_PER_SECOND) as u64;
+                (amount / NANOS_PER_SECOND) as u64
+            }
+        };
+        self.count_frames(frames);
+        frames
+    }
+
+    /// Zeroes both timers and starts counting time over from now. The frame count is kept.
+    pub fn reset(&mut self) {
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.rounding_remainder = 0.0;
+        self.remainder_nanos = 0;
+        self.last_update = self.clock.now();
+    }
+
+    /// Counts exactly one frame, ignoring the time that passed since the last update.
+    pub fn tick(&mut self) {
+        self.count_frames(1);
+        self.last_update = self.clock.now();
+    }
+
+    fn count_frames(&mut self, frames: u64) {
+        self.frames += frames;
+        let amount = frames.min(u8::MAX as u64) as u8;
+
+        if self.delay_timer > amount {
+            self.delay_timer -= amount;
+        } else {
+            self.delay_timer = 0;
+        }
+        if self.sound_timer > amount {
+            self.sound_timer -= amount;
+        } else {
+            self.sound_timer = 0;
+        }
+    }
+}
+
+/// xorshift64, small and deterministic for a given seed.
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck on zero
+        Self { state: seed.max(1) }
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 56) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    const PROGRAM: [u8; 10] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+
+    #[test]
+    fn load_program() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&PROGRAM);
+
+        for (i, byte) in PROGRAM.iter().enumerate() {
+            assert_eq!(interpreter.memory[PC_START_ADDRESS + i], *byte)
+        }
+    }
+
+    #[test]
+    fn load_empty_program() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[]);
+        assert!(interpreter.program_bytes().is_empty());
+        assert_eq!(
+            interpreter.run_cycles(1),
+            RunOutcome::Fault(Fault::DecodeError {
+                pc: PC_START_ADDRESS,
+                opcode: 0x0000
+            })
+        );
+    }
+
+    #[test]
+    fn program_bytes() {
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter.program_bytes().is_empty());
+
+        interpreter.load_program(&PROGRAM);
+        assert_eq!(interpreter.program_bytes(), PROGRAM);
+    }
+
+    #[test]
+    fn set_memory() {
+        // I = 0x300, draw
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0xA3, 0x00, 0xD0, 0x02]);
+        assert_eq!(
+            interpreter.set_memory(0x300, &[0b1100_0011, 0b0011_1100]),
+            Ok(())
+        );
+        assert_eq!(interpreter.run_cycles(2), RunOutcome::Completed);
+        assert_screen(
+            &interpreter,
+            "
+            ##....##
+            ..####..
+            ",
+        );
+
+        assert_eq!(
+            interpreter.set_memory(MEMORY_SIZE - 1, &[1, 2]),
+            Err(MemError::OutOfBounds(MEMORY_SIZE - 1))
+        );
+        assert_eq!(interpreter.memory[MEMORY_SIZE - 1], 0);
+        assert_eq!(
+            interpreter.set_memory(usize::MAX, &[1]),
+            Err(MemError::OutOfBounds(usize::MAX))
+        );
+    }
+
+    #[test]
+    fn patch() {
+        // V0 += 1, jump 0x200
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x70, 0x01, 0x12, 0x00]);
+        assert_eq!(interpreter.run_cycles(2), RunOutcome::Completed);
+
+        assert_eq!(interpreter.patch(0x200, 0x1200), Ok(()));
+        assert_eq!(interpreter.decode_at(0x200), Ok(Instruction::Jump(0x200)));
+        assert_eq!(interpreter.run_cycles(10), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[0], 1);
+
+        assert_eq!(
+            interpreter.patch(MEMORY_SIZE - 1, 0x00E0),
+            Err(MemError::OutOfBounds(MEMORY_SIZE - 1))
+        );
+    }
+
+    #[test]
+    fn sprite_ascii() {
+        let interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.sprite_ascii(FONT_START_ADDRESS, 5),
+            Ok("####....\n\
+                #..#....\n\
+                #..#....\n\
+                #..#....\n\
+                ####....\n"
+                .to_string())
+        );
+        assert_eq!(interpreter.sprite_ascii(0x300, 0), Ok(String::new()));
+        assert_eq!(
+            interpreter.sprite_ascii(MEMORY_SIZE - 2, 3),
+            Err(MemError::OutOfBounds(MEMORY_SIZE - 2))
+        );
+        assert_eq!(
+            interpreter.sprite_ascii(usize::MAX, 1),
+            Err(MemError::OutOfBounds(usize::MAX))
+        );
+    }
+
+    #[test]
+    fn free_program_space() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.free_program_space(), 0xE00);
+        interpreter.load_program(&PROGRAM);
+        assert_eq!(interpreter.free_program_space(), 0xE00 - PROGRAM.len());
+        interpreter.load_program(&[0; MEMORY_SIZE - PC_START_ADDRESS]);
+        assert_eq!(interpreter.free_program_space(), 0);
+    }
+
+    #[test]
+    fn load_program_from_reader() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .load_program_from_reader(io::Cursor::new(PROGRAM.to_vec()))
+            .unwrap();
+        assert_eq!(interpreter.program_bytes(), PROGRAM);
+
+        let too_large = io::Cursor::new(vec![0; MEMORY_SIZE]);
+        let error = interpreter.load_program_from_reader(too_large).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(interpreter.program_bytes(), PROGRAM);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn load_program_gz() {
+        let write_gz = |name: &str, rom: &[u8]| {
+            let path = std::env::temp_dir().join(format!("chip8-{}-{name}", std::process::id()));
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+            encoder.write_all(rom).unwrap();
+            std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+            path
+        };
+        let rom = write_gz("rom.ch8.gz", &PROGRAM);
+        let too_large = write_gz("too-large.ch8.gz", &[0; MEMORY_SIZE]);
+
+        let mut interpreter = Interpreter::new();
+        let loaded = interpreter.load_program_gz(&rom);
+        let rejected = interpreter.load_program_gz(&too_large);
+        std::fs::remove_file(rom).unwrap();
+        std::fs::remove_file(too_large).unwrap();
+
+        loaded.unwrap();
+        assert_eq!(rejected.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(interpreter.program_bytes(), PROGRAM);
+    }
+
+    #[test]
+    fn verify_plain_rom() {
+        let info = Interpreter::verify_rom(&PROGRAM).unwrap();
+        assert_eq!(info.size, PROGRAM.len());
+        assert!(!info.super_chip);
+        assert_eq!(info.suggested_quirks, Quirks::cosmac_vip());
+    }
+
+    #[test]
+    fn verify_super_chip_rom() {
+        let info = Interpreter::verify_rom(&[0x00, 0xFF, 0x12, 0x02]).unwrap();
+        assert!(info.super_chip);
+        assert_eq!(info.suggested_quirks, Quirks::super_chip());
+
+        assert_eq!(Interpreter::verify_rom(&[]), Err(RomError::Empty));
+        let too_large = [0; MEMORY_SIZE - PC_START_ADDRESS + 1];
+        assert_eq!(
+            Interpreter::verify_rom(&too_large),
+            Err(RomError::TooLarge(too_large.len()))
+        );
+    }
+
+    #[test]
+    fn compatibility_check() {
+        let rom = [0x60, 0x01, 0xFF, 0xFF, 0x00, 0xE0, 0xE0, 0xFF, 0x12];
+        assert_eq!(
+            Interpreter::compatibility_check(&rom),
+            [(0x202, 0xFFFF), (0x206, 0xE0FF)]
+        );
+    }
+
+    #[test]
+    fn call_graph() {
+        const ROM: [u8; 14] = [
+            0x22, 0x06, // 0x200: call 0x206
+            0x22, 0x0C, // 0x202: call 0x20C
+            0x12, 0x04, // 0x204: jump 0x204
+            0x22, 0x0C, // 0x206: call 0x20C
+            0x22, 0x0C, // 0x208: call 0x20C
+            0x00, 0xEE, // 0x20A: return
+            0x00, 0xEE, // 0x20C: return
+        ];
+        let graph = Interpreter::call_graph(&ROM);
+        assert_eq!(graph.len(), 2);
+        assert_eq!(graph[&0x200], [0x206, 0x20C]);
+        assert_eq!(graph[&0x206], [0x20C]);
+        assert!(Interpreter::call_graph(&PROGRAM).is_empty());
+    }
+
+    #[test]
+    fn swap_program() {
+        let rom_a = [0x60, 0x0A, 0x61, 0x0B, 0xA3, 0x00, 0x12, 0x06];
+        let rom_b = [0x62, 0x0C];
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            clock_hz: 900,
+            quirks: Quirks::xo_chip(),
+            ..Default::default()
+        });
+        interpreter.load_program(&rom_a);
+        assert_eq!(interpreter.run_cycles(4), RunOutcome::Completed);
+
+        interpreter.swap_program(&rom_b);
+        assert_eq!(interpreter.program_bytes(), rom_b);
+        assert!(
+            interpreter.memory[PC_START_ADDRESS + 2..PC_START_ADDRESS + rom_a.len()]
+                .iter()
+                .all(|byte| *byte == 0)
+        );
+        assert_eq!(interpreter.pc, PC_START_ADDRESS);
+        assert_eq!(interpreter.i, 0);
+        assert_eq!(interpreter.registers, [0; 16]);
+        assert_eq!(interpreter.config.clock_hz, 900);
+        assert_eq!(interpreter.config.quirks, Quirks::xo_chip());
+
+        assert_eq!(interpreter.run_cycles(1), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[2], 0x0C);
+    }
+
+    #[test]
+    fn decode_at() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x60, 0x01, 0xA3, 0x00, 0xD0, 0x15]);
+
+        assert_eq!(
+            interpreter.decode_at(0x204),
+            Ok(Instruction::DrawSprite(0, 1, 5))
+        );
+        assert_eq!(
+            interpreter.decode_at(0x203),
+            Ok(Instruction::Unknown(0x00D0))
+        );
+        assert_eq!(interpreter.pc, PC_START_ADDRESS);
+        assert_eq!(interpreter.cycles(), 0);
+        assert_eq!(
+            interpreter.decode_at(MEMORY_SIZE - 1),
+            Err(MemError::OutOfBounds(MEMORY_SIZE - 1))
+        );
+        assert_eq!(
+            interpreter.decode_at(usize::MAX - 1),
+            Err(MemError::OutOfBounds(usize::MAX - 1))
+        );
+    }
+
+    #[test]
+    fn exec_raw() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.exec_raw(0x6005),
+            Ok(Instruction::SetRegister(0, 5))
+        );
+        assert_eq!(interpreter.registers[0], 5);
+        assert_eq!(interpreter.pc, PC_START_ADDRESS);
+        assert_eq!(interpreter.cycles(), 0);
+
+        assert_eq!(interpreter.exec_raw(0xF00A), Ok(Instruction::WaitForKey(0)));
+        assert_eq!(interpreter.pc, PC_START_ADDRESS);
+        assert_eq!(interpreter.exec_raw(0x1300), Ok(Instruction::Jump(0x300)));
+        assert_eq!(interpreter.pc, 0x300);
+        assert_eq!(
+            interpreter.exec_raw(0xFFFF),
+            Err(Fault::DecodeError {
+                pc: 0x300,
+                opcode: 0xFFFF
+            })
+        );
+        assert_eq!(
+            interpreter.exec_raw(0x3005),
+            Ok(Instruction::SkipIfEqual(0, 5))
+        );
+        assert_eq!(interpreter.pc, 0x302);
+        assert_eq!(interpreter.exec_raw(0x2400), Ok(Instruction::Call(0x400)));
+        assert_eq!(interpreter.exec_raw(0x00EE), Ok(Instruction::Return));
+        assert_eq!(interpreter.pc, 0x304);
+    }
+
+    #[test]
+    fn exec_raw_faults_report_pc() {
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            quirks: Quirks::cosmac_vip(),
+            ..Default::default()
+        });
+        let pc = interpreter.pc;
+        assert_eq!(
+            interpreter.exec_raw(0xD010),
+            Err(Fault::Unsupported {
+                pc,
+                instruction: Instruction::DrawLargeSprite(0, 1)
+            })
+        );
+        assert_eq!(interpreter.pc, pc);
+
+        interpreter.pc = 0;
+        assert_eq!(
+            interpreter.exec_raw(0xD010),
+            Err(Fault::Unsupported {
+                pc: 0,
+                instruction: Instruction::DrawLargeSprite(0, 1)
+            })
+        );
+    }
+
+    #[test]
+    fn fetch_instruction() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&PROGRAM);
+
+        assert_eq!(interpreter.fetch_instruction(), 1);
+        assert_eq!(interpreter.fetch_instruction(), 0b0000001000000011);
+        assert_eq!(interpreter.fetch_instruction(), 0b0000010000000101);
+        assert_eq!(interpreter.fetch_instruction(), 0b0000011000000111);
+        assert_eq!(interpreter.fetch_instruction(), 0b0000100000001001);
+    }
+
+    #[test]
+    fn instruction_from_raw() {
+        assert_eq!(Instruction::from_raw(0x00E0), Instruction::ClearScreen);
+        assert_eq!(Instruction::from_raw(0x00EE), Instruction::Return);
+        assert_eq!(Instruction::from_raw(0x1FFF), Instruction::Jump(0x0FFF));
+        assert_eq!(Instruction::from_raw(0x2ABC), Instruction::Call(0x0ABC));
+        assert_eq!(
+            Instruction::from_raw(0x3A12),
+            Instruction::SkipIfEqual(0xA, 0x12)
+        );
+        assert_eq!(
+            Instruction::from_raw(0x4B34),
+            Instruction::SkipIfNotEqual(0xB, 0x34)
+        );
+        assert_eq!(
+            Instruction::from_raw(0x6502),
+            Instruction::SetRegister(5, 2)
+        );
+        assert_eq!(
+            Instruction::from_raw(0x70FF),
+            Instruction::AddToRegister(0, 0xFF)
+        );
+        assert_eq!(Instruction::from_raw(0x8125), Instruction::Subtract(1, 2));
+        assert_eq!(Instruction::from_raw(0x8126), Instruction::ShiftRight(1, 2));
+        assert_eq!(
+            Instruction::from_raw(0x8127),
+            Instruction::SubtractReversed(1, 2)
+        );
+        assert_eq!(Instruction::from_raw(0x834E), Instruction::ShiftLeft(3, 4));
+        assert_eq!(Instruction::from_raw(0xAFFF), Instruction::SetI(0x0FFF));
+        assert_eq!(Instruction::from_raw(0xC30F), Instruction::Random(3, 0x0F));
+        assert_eq!(
+            Instruction::from_raw(0xD123),
+            Instruction::DrawSprite(1, 2, 3)
+        );
+        assert_eq!(Instruction::from_raw(0xE79E), Instruction::SkipIfKey(7));
+        assert_eq!(Instruction::from_raw(0xE8A1), Instruction::SkipIfNotKey(8));
+        assert_eq!(Instruction::from_raw(0xF301), Instruction::SelectPlanes(3));
+        assert_eq!(Instruction::from_raw(0xF002), Instruction::LoadAudioPattern);
+        assert_eq!(Instruction::from_raw(0xF102), Instruction::Unknown(0xF102));
+        assert_eq!(Instruction::from_raw(0xF53A), Instruction::SetPitch(5));
+        assert_eq!(Instruction::from_raw(0xF107), Instruction::GetDelayTimer(1));
+        assert_eq!(Instruction::from_raw(0xF40A), Instruction::WaitForKey(4));
+        assert_eq!(Instruction::from_raw(0xF215), Instruction::SetDelayTimer(2));
+        assert_eq!(Instruction::from_raw(0xF318), Instruction::SetSoundTimer(3));
+        assert_eq!(
+            Instruction::from_raw(0xFA29),
+            Instruction::FontCharacter(0xA)
+        );
+        assert_eq!(
+            Instruction::from_raw(0xF633),
+            Instruction::BinaryCodedDecimal(6)
+        );
+        assert_eq!(
+            Instruction::from_raw(0xF255),
+            Instruction::StoreRegisters(2)
+        );
+        assert_eq!(Instruction::from_raw(0xF265), Instruction::LoadRegisters(2));
+    }
+
+    #[test]
+    fn supported_opcodes() {
+        let opcodes = Interpreter::supported_opcodes();
+        assert!(!opcodes.is_empty());
+        let mnemonics: Vec<&str> = opcodes.iter().map(|info| info.mnemonic).collect();
+        assert!(mnemonics.contains(&"CLS"));
+        assert!(mnemonics.contains(&"JP"));
+
+        for opcode in 0..=u16::MAX {
+            let decodes = !matches!(Instruction::from_raw(opcode), Instruction::Unknown(_));
+            let listed = opcodes.iter().filter(|info| info.matches(opcode)).count();
+            assert_eq!(listed, decodes as usize, "{opcode:#06X}");
+        }
+    }
+
+    #[test]
+    fn opcode_stats() {
+        // 0x200: V0 = 1, 0x202: V1 = 2, 0x204: jump to 0x200
+        const LOOP: [u8; 6] = [0x60, 0x01, 0x61, 0x02, 0x12, 0x00];
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&LOOP);
+        assert_eq!(interpreter.opcode_stats(), None);
+
+        interpreter.enable_profiling();
+        assert_eq!(interpreter.run_cycles(9), RunOutcome::Completed);
+
+        let stats = interpreter.opcode_stats().unwrap();
+        assert_eq!(stats[0x1], 3);
+        assert_eq!(stats[0x6], 6);
+        assert_eq!(stats.iter().sum::<u64>(), 9);
+    }
+
+    #[test]
+    fn export_opcode_stats() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x60, 0x01, 0x61, 0x02, 0x12, 0x00]);
+        interpreter.enable_profiling();
+        assert_eq!(interpreter.run_cycles(9), RunOutcome::Completed);
+
+        let json = interpreter.export_opcode_stats(StatsFormat::Json);
+        let entries: HashMap<&str, u64> = json
+            .strip_prefix('{')
+            .and_then(|json| json.strip_suffix('}'))
+            .expect("not a JSON object")
+            .split(',')
+            .map(|entry| {
+                let (key, count) = entry.split_once(':').unwrap();
+                (key.trim_matches('"'), count.parse().unwrap())
+            })
+            .collect();
+        assert_eq!(entries.len(), 16);
+        assert_eq!(entries["1"], 3);
+        assert_eq!(entries["6"], 6);
+        assert_eq!(entries["F"], 0);
+
+        let csv = interpreter.export_opcode_stats(StatsFormat::Csv);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 17);
+        assert_eq!(lines[0], "opcode,count");
+        assert_eq!(lines[7], "6,6");
+    }
+
+    // 0x200: V0 = 1, 0x202: unknown opcode, 0x204: V1 = 2
+    const UNKNOWN_OPCODE: [u8; 6] = [0x60, 0x01, 0xFF, 0xFF, 0x61, 0x02];
+
+    #[test]
+    fn unimplemented_policy_fault() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&UNKNOWN_OPCODE);
+
+        assert_eq!(
+            interpreter.run_cycles(3),
+            RunOutcome::Fault(Fault::DecodeError {
+                pc: PC_START_ADDRESS + 2,
+                opcode: 0xFFFF
+            })
+        );
+        assert_eq!(interpreter.pc, PC_START_ADDRESS + 2);
+        assert_eq!(interpreter.registers[0], 1);
+        assert_eq!(interpreter.registers[1], 0);
+    }
+
+    #[test]
+    fn unimplemented_policy_skip() {
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            unimplemented_policy: UnimplementedPolicy::Skip,
+            ..Default::default()
+        });
+        interpreter.load_program(&UNKNOWN_OPCODE);
+
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        assert_eq!(interpreter.pc, PC_START_ADDRESS + 6);
+        assert_eq!(interpreter.registers[0], 1);
+        assert_eq!(interpreter.registers[1], 2);
+    }
+
+    #[test]
+    fn last_fault() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&UNKNOWN_OPCODE);
+        assert_eq!(interpreter.run_cycles(1), RunOutcome::Completed);
+        assert_eq!(interpreter.last_fault(), None);
+
+        let fault = Fault::DecodeError {
+            pc: PC_START_ADDRESS + 2,
+            opcode: 0xFFFF,
+        };
+        assert_eq!(interpreter.run_cycles(1), RunOutcome::Fault(fault.clone()));
+        // Polling again keeps the fault around.
+        assert_eq!(interpreter.run_cycles(1), RunOutcome::Fault(fault.clone()));
+        assert_eq!(interpreter.last_fault(), Some(&fault));
+
+        interpreter.clear_fault();
+        assert_eq!(interpreter.last_fault(), None);
+    }
+
+    #[test]
+    fn hit_unimplemented_flag() {
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            unimplemented_policy: UnimplementedPolicy::Skip,
+            ..Default::default()
+        });
+        interpreter.load_program(&UNKNOWN_OPCODE);
+
+        assert_eq!(interpreter.run_cycles(1), RunOutcome::Completed);
+        assert!(!interpreter.hit_unimplemented());
+        assert_eq!(interpreter.run_cycles(2), RunOutcome::Completed);
+        assert!(interpreter.hit_unimplemented());
+
+        interpreter.clear_hit_unimplemented();
+        assert!(!interpreter.hit_unimplemented());
+    }
+
+    #[test]
+    fn clear_screen_respects_selected_planes() {
+        const PROGRAM: [u8; 14] = [
+            0xA0, 0x50, // I = font glyph 0
+            0xF1, 0x01, // select plane 1
+            0xD0, 0x05, // draw
+            0xF2, 0x01, // select plane 2
+            0xD0, 0x05, // draw
+            0xF1, 0x01, // select plane 1
+            0x00, 0xE0, // clear
+        ];
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&PROGRAM);
+
+        assert_eq!(interpreter.run_cycles(6), RunOutcome::Completed);
+        assert_eq!(interpreter.screen_buffer[0], 0b11);
+        assert_eq!(interpreter.run_cycles(1), RunOutcome::Completed);
+
+        for (index, pixel) in interpreter.screen().iter().enumerate() {
+            let (x, y) = (index % SCREEN_WIDTH, index / SCREEN_WIDTH);
+            let glyph_bit = y < 5 && x < 8 && FONT[y] & (0x80 >> x) != 0;
+            assert_eq!(*pixel, if glyph_bit { 0b10 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn planes_are_read_back_separately() {
+        const PROGRAM: [u8; 12] = [
+            0xA0, 0x50, // I = font glyph 0
+            0xF1, 0x01, // select plane 1
+            0xD0, 0x05, // draw
+            0xA0, 0x55, // I = font glyph 1
+            0xF2, 0x01, // select plane 2
+            0xD0, 0x05, // draw
+        ];
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&PROGRAM);
+        assert_eq!(interpreter.run_cycles(6), RunOutcome::Completed);
+
+        for (plane, glyph) in [(0, 0), (1, 1)] {
+            let bits = interpreter.plane(plane);
+            assert_eq!(bits.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+            for (index, bit) in bits.iter().enumerate() {
+                let (x, y) = (index % SCREEN_WIDTH, index / SCREEN_WIDTH);
+                let lit = y < 5 && x < 8 && FONT[glyph * 5 + y] & (0x80 >> x) != 0;
+                assert_eq!(*bit, lit as u8, "plane {plane} at ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn lores_sprite_is_doubled_on_hires_display() {
+        // V0 = 1, I = font glyph 0, draw at (1, 1)
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            quirks: Quirks::super_chip(),
+            ..Default::default()
+        });
+        interpreter.load_program(&[0x60, 0x01, 0xA0, 0x50, 0xD0, 0x05]);
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+
+        assert_eq!(interpreter.display_size(), (HIRES_WIDTH, HIRES_HEIGHT));
+        assert_screen(
+            &interpreter,
+            "
+            ..........
+            ..........
+            ..########
+            ..########
+            ..##....##
+            ..##....##
+            ..##....##
+            ..##....##
+            ..##....##
+            ..##....##
+            ..########
+            ..########
+            ",
+        );
+    }
+
+    #[test]
+    fn hires_mode_switches_display_size() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x00, 0xFF, 0x00, 0xFE]);
+        assert_eq!(interpreter.display_size(), (SCREEN_WIDTH, SCREEN_HEIGHT));
+        assert_eq!(interpreter.run_cycles(1), RunOutcome::Completed);
+        assert_eq!(interpreter.display_size(), (HIRES_WIDTH, HIRES_HEIGHT));
+        assert_eq!(interpreter.screen().len(), HIRES_WIDTH * HIRES_HEIGHT);
+        assert_eq!(interpreter.run_cycles(1), RunOutcome::Completed);
+        assert_eq!(interpreter.display_size(), (SCREEN_WIDTH, SCREEN_HEIGHT));
+    }
+
+    #[test]
+    fn binary_coded_decimal() {
+        // I = 0x300, V0 = value, BCD of V0
+        for (value, digits) in [
+            (0, [0, 0, 0]),
+            (5, [0, 0, 5]),
+            (9, [0, 0, 9]),
+            (99, [0, 9, 9]),
+            (100, [1, 0, 0]),
+            (255, [2, 5, 5]),
+        ] {
+            let mut interpreter = Interpreter::new();
+            interpreter.load_program(&[0xA3, 0x00, 0x60, value, 0xF0, 0x33]);
+            interpreter.memory[0x303] = 0xAA;
+            assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+            assert_eq!(
+                interpreter.memory[0x300..0x304],
+                [digits[0], digits[1], digits[2], 0xAA]
+            );
+            assert_eq!(interpreter.i, 0x300);
+        }
+    }
+
+    #[test]
+    fn memory_mapped_io() {
+        // I = 0xE00, V0..V1 = [0xE00, 0xE01], V0 += 1, [0xE00, 0xE01] = V0..V1
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0xAE, 0x00, 0xF1, 0x65, 0x70, 0x01, 0xAE, 0x00, 0xF1, 0x55]);
+        let writes = Arc::new(Mutex::new(Vec::new()));
+        let log = writes.clone();
+        interpreter.map_io(0xE00..0xE02, move |access| match access {
+            IoAccess::Read(address) => address as u8 + 0x10,
+            IoAccess::Write(..) => {
+                log.lock().unwrap().push(access);
+                0
+            }
+        });
+
+        assert_eq!(interpreter.run_cycles(5), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[..2], [0x11, 0x11]);
+        assert_eq!(
+            *writes.lock().unwrap(),
+            [IoAccess::Write(0xE00, 0x11), IoAccess::Write(0xE01, 0x11)]
+        );
+        assert_eq!(interpreter.memory[0xE00..0xE02], [0, 0]);
+    }
+
+    /// Draws glyph 0 at the top left, then runs `scroll`.
+    fn scrolled_glyph(quirks: Quirks, scroll: [u8; 2]) -> Interpreter {
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            quirks: Quirks {
+                lores_scaling: false,
+                ..quirks
+            },
+            ..Default::default()
+        });
+        interpreter.load_program(&[0xA0, 0x50, 0xD0, 0x05, scroll[0], scroll[1]]);
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        interpreter
+    }
+
+    #[test]
+    fn scroll_clears_vacated_area() {
+        let interpreter = scrolled_glyph(Quirks::super_chip(), [0x00, 0xC2]);
+        assert_screen(
+            &interpreter,
+            "
+            ....
+            ....
+            ####
+            #..#
+            #..#
+            #..#
+            ####
+            ",
+        );
+
+        let interpreter = scrolled_glyph(Quirks::super_chip(), [0x00, 0xFC]);
+        assert_screen(&interpreter, "");
+    }
+
+    #[test]
+    fn scroll_wraps_vacated_area() {
+        let interpreter = scrolled_glyph(Quirks::xo_chip(), [0x00, 0xFB]);
+        assert_screen(
+            &interpreter,
+            "
+            ....####
+            ....#..#
+            ....#..#
+            ....#..#
+            ....####
+            ",
+        );
+
+        let interpreter = scrolled_glyph(Quirks::xo_chip(), [0x00, 0xFC]);
+        let row = |y: usize| &interpreter.screen()[y * SCREEN_WIDTH..(y + 1) * SCREEN_WIDTH];
+        assert_eq!(row(0)[SCREEN_WIDTH - 4..], [1, 1, 1, 1]);
+        assert_eq!(row(1)[SCREEN_WIDTH - 4..], [1, 0, 0, 1]);
+        assert!(row(5).iter().all(|pixel| *pixel == 0));
+    }
+
+    #[test]
+    fn debug_summarizes_state() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x6A, 0x42, 0x22, 0x06, 0x00, 0x00, 0xA1, 0x23]);
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+
+        let debug = format!("{interpreter:?}");
+        assert!(debug.contains("pc: 0x208"), "{debug}");
+        assert!(debug.contains("i: 0x123"), "{debug}");
+        assert!(debug.contains("42"), "{debug}");
+        assert!(debug.contains("stack_depth: 1"), "{debug}");
+        assert!(debug.len() < 500, "{debug}");
+    }
+
+    #[test]
+    fn state_hash() {
+        // I = glyph 0, V0 = 3, draw, call 0x20A, 0x20A: jump 0x20A
+        const PROGRAM: [u8; 12] = [
+            0xA0, 0x50, 0x60, 0x03, 0xD0, 0x05, 0x22, 0x0A, 0x00, 0x00, 0x12, 0x0A,
+        ];
+        let run = || {
+            let mut interpreter = Interpreter::for_test_rom();
+            interpreter.load_program(&PROGRAM);
+            assert_eq!(interpreter.run_cycles(6), RunOutcome::Completed);
+            interpreter
+        };
+        let (a, mut b) = (run(), run());
+        assert_eq!(a.state_hash(), b.state_hash());
+        assert_ne!(a.state_hash(), Interpreter::for_test_rom().state_hash());
+
+        b.registers[5] = 1;
+        assert_ne!(a.state_hash(), b.state_hash());
+
+        // Pinned, as the hash mustn't change between releases or platforms.
+        assert_eq!(a.state_hash(), 0xF3B9_BE2B_D0AC_3691);
+    }
+
+    #[test]
+    fn named_register_access() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.set_v(0xF, 42), Ok(()));
+        assert_eq!(interpreter.v(0xF), Some(42));
+        assert_eq!(interpreter.registers[0xF], 42);
+
+        assert_eq!(interpreter.v(16), None);
+        assert_eq!(interpreter.set_v(16, 1), Err(Fault::InvalidRegister(16)));
+    }
+
+    #[test]
+    fn turbo_advances_timers_by_emulated_time() {
+        let clock = MockClock::new();
+        let config = InterpreterConfig {
+            clock_hz: 600,
+            ..Default::default()
+        };
+        let mut interpreter = Interpreter::with_clock(config, Box::new(clock.clone()));
+        interpreter.load_program(&[0x12, 0x00]);
+        interpreter.timers.delay_timer = 120;
+
+        // The wall clock doesn't move, so throttled frames leave the timer alone.
+        for _ in 0..10 {
+            assert_eq!(interpreter.tick_frame(), RunOutcome::Completed);
+        }
+        assert_eq!(interpreter.timers.delay_timer, 120);
+
+        interpreter.set_turbo(true);
+        for _ in 0..60 {
+            assert_eq!(interpreter.tick_frame(), RunOutcome::Completed);
+        }
+        assert!(approx_equal_u8(interpreter.timers.delay_timer, 60, 1));
+
+        // Throttled frames follow the clock again.
+        clock.advance(Duration::from_millis(100));
+        interpreter.set_turbo(false);
+        interpreter.tick_frame();
+        assert!(approx_equal_u8(interpreter.timers.delay_timer, 54, 1));
+    }
+
+    #[test]
+    fn store_and_load_registers() {
+        const PROGRAM: [u8; 12] = [
+            0x60, 0x0A, // V0 = 0x0A
+            0x61, 0x0B, // V1 = 0x0B
+            0xA3, 0x00, // I = 0x300
+            0xF1, 0x55, // store V0..V1
+            0x60, 0x00, // V0 = 0
+            0xF0, 0x65, // load V0
+        ];
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&PROGRAM);
+
+        assert_eq!(interpreter.run_cycles(4), RunOutcome::Completed);
+        assert_eq!(interpreter.memory[0x300..0x302], [0x0A, 0x0B]);
+        assert_eq!(interpreter.i, 0x302);
+
+        interpreter.i = 0x300;
+        assert_eq!(interpreter.run_cycles(2), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[0], 0x0A);
+    }
+
+    #[test]
+    fn preset_shift_quirk() {
+        assert!(Quirks::cosmac_vip().shift_uses_vy);
+        assert!(!Quirks::super_chip().shift_uses_vy);
+        assert!(Quirks::xo_chip().shift_uses_vy);
+        assert_eq!(Quirks::default(), Quirks::cosmac_vip());
+    }
+
+    #[test]
+    fn jump_with_offset() {
+        // V0 = 0x10, V3 = 0x20, jump to 0x300 plus V0 or V3
+        let program = [0x60, 0x10, 0x63, 0x20, 0xB3, 0x00];
+        for (quirks, target) in [(Quirks::cosmac_vip(), 0x310), (Quirks::super_chip(), 0x320)] {
+            let mut interpreter = Interpreter::with_config(InterpreterConfig {
+                quirks,
+                ..Default::default()
+            });
+            interpreter.load_program(&program);
+            assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+            assert_eq!(interpreter.pc, target);
+        }
+        assert_eq!(
+            Instruction::from_raw(0xB300),
+            Instruction::JumpWithOffset(0x300)
+        );
+        assert_eq!(
+            Instruction::JumpWithOffset(0x300).to_string(),
+            "JP V0, 0x300"
+        );
+    }
+
+    #[test]
+    fn shift_follows_quirk() {
+        // V0 = 0x81, V1 = 0x03, V0 = V1 >> 1, V2 = 0x81, V2 = V1 << 1
+        const PROGRAM: [u8; 10] = [0x60, 0x81, 0x61, 0x03, 0x80, 0x16, 0x62, 0x81, 0x82, 0x1E];
+        for (quirks, shifted_right, shifted_left, flag) in [
+            (Quirks::cosmac_vip(), 0x01, 0x06, 0),
+            (Quirks::super_chip(), 0x40, 0x02, 1),
+        ] {
+            let mut interpreter = Interpreter::with_config(InterpreterConfig {
+                quirks,
+                ..Default::default()
+            });
+            interpreter.load_program(&PROGRAM);
+            assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+            assert_eq!(interpreter.registers[0], shifted_right);
+            assert_eq!(interpreter.registers[0xF], 1);
+            assert_eq!(interpreter.run_cycles(2), RunOutcome::Completed);
+            assert_eq!(interpreter.registers[2], shifted_left);
+            assert_eq!(interpreter.registers[0xF], flag);
+        }
+    }
+
+    #[test]
+    fn subtract_sets_vf_when_there_is_no_borrow() {
+        // V0 = a, V1 = b, then either V0 = V0 - V1 or V0 = V1 - V0
+        for (low, a, b, difference, flag) in [
+            (0x15, 5, 3, 2, 1),
+            (0x15, 3, 3, 0, 1),
+            (0x15, 3, 5, 0xFE, 0),
+            (0x17, 5, 3, 0xFE, 0),
+            (0x17, 3, 3, 0, 1),
+            (0x17, 3, 5, 2, 1),
+        ] {
+            let mut interpreter = Interpreter::new();
+            interpreter.load_program(&[0x60, a, 0x61, b, 0x80, low]);
+            assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+            assert_eq!(
+                interpreter.registers[0], difference,
+                "80{low:02X} with {a} and {b}"
+            );
+            assert_eq!(
+                interpreter.registers[0xF], flag,
+                "80{low:02X} with {a} and {b}"
+            );
+        }
+    }
+
+    #[test]
+    fn subtract_with_vf_operand() {
+        // VF = 5, V1 = 3, V1 = V1 - VF
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x6F, 0x05, 0x61, 0x03, 0x81, 0xF5]);
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[1], 0xFE);
+        assert_eq!(interpreter.registers[0xF], 0);
+
+        // V1 = 3, VF = 5, VF = VF - V1
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x61, 0x03, 0x6F, 0x05, 0x8F, 0x15]);
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[0xF], 1);
+
+        // V1 = 3, VF = 5, VF = V1 - VF
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x61, 0x03, 0x6F, 0x05, 0x8F, 0x17]);
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[0xF], 0);
+    }
+
+    #[test]
+    fn shift_with_vf_operand() {
+        // VF = 0x81, V1 = 0x40, V1 = VF << 1
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x6F, 0x81, 0x61, 0x40, 0x81, 0xFE]);
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[1], 0x02);
+        assert_eq!(interpreter.registers[0xF], 1);
+
+        // V1 = 0x40, VF = V1 << 1
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x61, 0x40, 0x8F, 0x1E]);
+        assert_eq!(interpreter.run_cycles(2), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[0xF], 0);
+    }
+
+    #[test]
+    fn store_registers_without_incrementing_i() {
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            quirks: Quirks::super_chip(),
+            ..Default::default()
+        });
+        interpreter.load_program(&[0xA3, 0x00, 0xF3, 0x55]);
+
+        assert_eq!(interpreter.run_cycles(2), RunOutcome::Completed);
+        assert_eq!(interpreter.i, 0x300);
+    }
+
+    #[test]
+    fn sprites_wrap_without_clipping() {
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            quirks: Quirks::xo_chip(),
+            ..Default::default()
+        });
+        // Draw the top two rows of glyph 0 (####, #..#) at x = 62, y = 31
+        interpreter.load_program(&[0x60, 0x3E, 0x61, 0x1F, 0xA0, 0x50, 0xD0, 0x12]);
+
+        assert_eq!(interpreter.run_cycles(4), RunOutcome::Completed);
+        let row = |y: usize| &interpreter.screen_buffer[y * SCREEN_WIDTH..(y + 1) * SCREEN_WIDTH];
+        assert_eq!(row(31)[62..], [1, 1]);
+        assert_eq!(row(31)[..3], [1, 1, 0]);
+        assert_eq!(row(0)[62..], [1, 0]);
+        assert_eq!(row(0)[..3], [0, 1, 0]);
+    }
+
+    /// Draws the top two rows of glyph 0 (####, #..#) at x = 62, y = 31.
+    fn corner_glyph(clip_x: bool, clip_y: bool) -> Interpreter {
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            quirks: Quirks {
+                clip_x,
+                clip_y,
+                ..Quirks::xo_chip()
+            },
+            ..Default::default()
+        });
+        interpreter.load_program(&[0x60, 0x3E, 0x61, 0x1F, 0xA0, 0x50, 0xD0, 0x12]);
+        assert_eq!(interpreter.run_cycles(4), RunOutcome::Completed);
+        interpreter
+    }
+
+    #[test]
+    fn sprites_wrap_x_and_clip_y() {
+        let interpreter = corner_glyph(false, true);
+        let row = |y: usize| &interpreter.screen_buffer[y * SCREEN_WIDTH..(y + 1) * SCREEN_WIDTH];
+        assert_eq!(row(31)[62..], [1, 1]);
+        assert_eq!(row(31)[..3], [1, 1, 0]);
+        assert!(row(0).iter().all(|pixel| *pixel == 0));
+    }
+
+    #[test]
+    fn sprites_clip_x_and_wrap_y() {
+        let interpreter = corner_glyph(true, false);
+        let row = |y: usize| &interpreter.screen_buffer[y * SCREEN_WIDTH..(y + 1) * SCREEN_WIDTH];
+        assert_eq!(row(31)[62..], [1, 1]);
+        assert_eq!(row(31)[..3], [0, 0, 0]);
+        assert_eq!(row(0)[62..], [1, 0]);
+        assert_eq!(row(0)[..3], [0, 0, 0]);
+    }
+
+    #[test]
+    fn bundle_round_trip() {
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            clock_hz: 1000,
+            quirks: Quirks::xo_chip(),
+            ..Default::default()
+        });
+        interpreter.load_program(&PROGRAM);
+
+        let bundle = interpreter.save_bundle();
+        let loaded = Interpreter::load_bundle(&bundle).unwrap();
+        assert_eq!(loaded.config.clock_hz, 1000);
+        assert_eq!(loaded.config.quirks, Quirks::xo_chip());
+        assert_eq!(loaded.program_len, PROGRAM.len());
+        assert_eq!(
+            loaded.memory[PC_START_ADDRESS..PC_START_ADDRESS + 10],
+            PROGRAM
+        );
+    }
+
+    #[test]
+    fn bundle_ignores_unknown_fields() {
+        let bundle = "chip8-bundle\nfuture_field=whatever\nclock_hz=500\nrom=00E0\n";
+        let loaded = Interpreter::load_bundle(bundle).unwrap();
+        assert_eq!(loaded.config.clock_hz, 500);
+        assert_eq!(
+            loaded.memory[PC_START_ADDRESS..PC_START_ADDRESS + 2],
+            [0x00, 0xE0]
+        );
+
+        assert_eq!(
+            Interpreter::load_bundle("clock_hz=500").err(),
+            Some(BundleError::MissingHeader)
+        );
+        assert_eq!(
+            Interpreter::load_bundle("chip8-bundle\nclock_hz=fast\nrom=00").err(),
+            Some(BundleError::InvalidValue("clock_hz".to_string()))
+        );
+    }
+
+    #[test]
+    fn bundle_rejects_bad_values() {
+        let rom_error = Some(BundleError::InvalidValue("rom".to_string()));
+        assert_eq!(
+            Interpreter::load_bundle("chip8-bundle\nrom=0€\n").err(),
+            rom_error
+        );
+        let too_large = format!("chip8-bundle\nrom={}\n", "00".repeat(0xE01));
+        assert_eq!(Interpreter::load_bundle(&too_large).err(), rom_error);
+        let fits = format!("chip8-bundle\nrom={}\n", "00".repeat(0xE00));
+        assert!(Interpreter::load_bundle(&fits).is_ok());
+
+        assert_eq!(
+            Interpreter::load_bundle("chip8-bundle\nclock_hz=0\nrom=00E0").err(),
+            Some(BundleError::InvalidValue("clock_hz".to_string()))
+        );
+    }
+
+    #[test]
+    fn write_watch_records_stores_into_program() {
+        const PROGRAM: [u8; 10] = [
+            0x60, 0x12, // V0 = 0x12
+            0xA2, 0x08, // I = 0x208, inside the program
+            0xF0, 0x55, // store V0
+            0xA3, 0x00, // I = 0x300, past the program
+            0xF0, 0x55, // store V0
+        ];
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&PROGRAM);
+        interpreter.enable_write_watch();
+
+        assert_eq!(interpreter.run_cycles(5), RunOutcome::Completed);
+        let modified: Vec<_> = interpreter.modified_addresses().unwrap().iter().collect();
+        assert_eq!(modified, [&0x208]);
+    }
+
+    #[test]
+    fn step_returns_executed_instruction() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x60, 0x01, 0xA3, 0x00, 0x00, 0xE0, 0x12, 0x00]);
+
+        let trace: Vec<_> = (0..5).map(|_| interpreter.step().unwrap()).collect();
+        assert_eq!(
+            trace,
+            [
+                Instruction::SetRegister(0, 1),
+                Instruction::SetI(0x300),
+                Instruction::ClearScreen,
+                Instruction::Jump(0x200),
+                Instruction::SetRegister(0, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn call_and_return() {
+        // 0x200: call 0x206, 0x202: V1 = 1, 0x204: jump 0x204, 0x206: V0 = 1, 0x208: return
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x22, 0x06, 0x61, 0x01, 0x12, 0x04, 0x60, 0x01, 0x00, 0xEE]);
+
+        assert_eq!(interpreter.run_cycles(4), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[..2], [1, 1]);
+        assert_eq!(interpreter.pc, 0x204);
+
+        interpreter.pc = 0x208;
+        assert_eq!(
+            interpreter.run_cycles(1),
+            RunOutcome::Fault(Fault::StackUnderflow)
+        );
+    }
+
+    // Calls itself forever
+    const RECURSION: [u8; 2] = [0x22, 0x00];
+
+    #[test]
+    fn stack_depth_16_faults_on_17th_call() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&RECURSION);
+
+        assert_eq!(interpreter.run_cycles(16), RunOutcome::Completed);
+        assert_eq!(
+            interpreter.run_cycles(1),
+            RunOutcome::Fault(Fault::StackOverflow)
+        );
+    }
+
+    #[test]
+    fn misaligned_pc_faults() {
+        // Jump to 0x203, which would execute 0x6001
+        const ODD_JUMP: [u8; 6] = [0x12, 0x03, 0x00, 0x60, 0x01, 0x00];
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            require_aligned_pc: true,
+            ..Default::default()
+        });
+        interpreter.load_program(&ODD_JUMP);
+        assert_eq!(
+            interpreter.run_cycles(2),
+            RunOutcome::Fault(Fault::MisalignedPc(0x203))
+        );
+
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&ODD_JUMP);
+        assert_eq!(interpreter.run_cycles(2), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[0], 1);
+    }
+
+    #[test]
+    fn fill_pattern() {
+        let interpreter = Interpreter::with_config(InterpreterConfig {
+            fill_pattern: 0xAA,
+            ..Default::default()
+        });
+        assert_eq!(interpreter.registers, [0xAA; 16]);
+        assert_eq!(interpreter.memory[0], 0xAA);
+        assert_eq!(interpreter.memory[PC_START_ADDRESS], 0xAA);
+        assert_eq!(interpreter.memory[MEMORY_SIZE - 1], 0xAA);
+        let font = &interpreter.memory[FONT_START_ADDRESS..FONT_START_ADDRESS + FONT.len()];
+        assert_eq!(font, FONT);
+    }
+
+    #[test]
+    fn stack_depth_48_allows_deeper_calls() {
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            stack_depth: 48,
+            ..Default::default()
+        });
+        interpreter.load_program(&RECURSION);
+
+        assert_eq!(interpreter.run_cycles(48), RunOutcome::Completed);
+        assert_eq!(
+            interpreter.run_cycles(1),
+            RunOutcome::Fault(Fault::StackOverflow)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "stack depth")]
+    fn stack_depth_is_validated() {
+        Interpreter::with_config(InterpreterConfig {
+            stack_depth: STACK_SIZE + 1,
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn spawned_interpreter_takes_keys_and_sends_frames() {
+        // Wait for a key and draw its font character at the top left.
+        let handle = Interpreter::spawn(|| {
+            let mut interpreter = Interpreter::for_test_rom();
+            interpreter.load_program(&[0xF0, 0x0A, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x06]);
+            interpreter
+        });
+        let first = handle
+            .frames()
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap();
+        assert!(first.iter().all(|pixel| *pixel == 0));
+
+        assert!(handle.send_key(KeyEvent::Down(8)));
+        let drawn = (0..100)
+            .map(|_| {
+                handle
+                    .frames()
+                    .recv_timeout(Duration::from_secs(5))
+                    .unwrap()
+            })
+            .find(|frame| frame[0] != 0)
+            .expect("the key was never drawn");
+        assert_eq!(&drawn[..5], &[1, 1, 1, 1, 0]);
+        assert_eq!(handle.stop(), RunOutcome::Completed);
+    }
+
+    #[test]
+    fn interpreter_moves_to_another_thread() {
+        let mut interpreter = Interpreter::with_config(InterpreterConfig::default());
+        // V0 = 8, I = glyph 8, draw
+        interpreter.load_program(&[0x60, 0x08, 0xF0, 0x29, 0xD1, 0x15]);
+
+        let interpreter = thread::spawn(move || {
+            let mut interpreter = interpreter;
+            assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+            interpreter
+        })
+        .join()
+        .unwrap();
+        assert_eq!(&interpreter.screen()[..5], &[1, 1, 1, 1, 0]);
+        assert_eq!(interpreter.cycles(), 3);
+    }
+
+    #[test]
+    fn advance_one_frame_decrements_timers_once() {
+        // Delay timer = 10, then loop.
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x60, 0x0A, 0xF0, 0x15, 0x12, 0x04]);
+
+        assert_eq!(interpreter.advance_one_frame(), RunOutcome::Completed);
+        assert_eq!(interpreter.timers.delay_timer, 9);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(interpreter.advance_one_frame(), RunOutcome::Completed);
+        assert_eq!(interpreter.timers.delay_timer, 8);
+        assert_eq!(interpreter.frames(), 2);
+        assert_eq!(
+            interpreter.cycles(),
+            2 * interpreter.cycles_per_frame() as u64
+        );
+    }
+
+    #[test]
+    fn run_frames_advances_timers_by_emulated_time() {
+        // Delay timer = 0xFF, then loop.
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x60, 0xFF, 0xF0, 0x15, 0x12, 0x04]);
+
+        assert_eq!(interpreter.run_frames(10), RunOutcome::Completed);
+        assert_eq!(
+            interpreter.cycles(),
+            10 * interpreter.cycles_per_frame() as u64
+        );
+        assert_eq!(interpreter.frames(), 10);
+        assert!(approx_equal_u8(
+            interpreter.timers.delay_timer,
+            0xFF - 10,
+            1
+        ));
+        assert!(!interpreter.turbo);
+    }
+
+    /// Run with `cargo test --release --features bench -- --nocapture run_frames_benchmark`.
+    #[cfg(feature = "bench")]
+    #[test]
+    fn run_frames_benchmark() {
+        let mut interpreter = Interpreter::for_test_rom();
+        interpreter.load_program(&DRAW_CLEAR_LOOP);
+
+        let start = Instant::now();
+        assert_eq!(interpreter.run_frames(60 * 60), RunOutcome::Completed);
+        let elapsed = start.elapsed();
+        println!(
+            "{} cycles in {elapsed:?}, {:.0} cycles per second",
+            interpreter.cycles(),
+            interpreter.cycles() as f64 / elapsed.as_secs_f64()
+        );
+    }
+
+    #[test]
+    fn run_for_returns_after_duration() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x12, 0x00]);
+
+        let start = Instant::now();
+        assert_eq!(
+            interpreter.run_for(Duration::from_millis(100)),
+            RunOutcome::Completed
+        );
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(100), "{elapsed:?}");
+        // Throttled to the clock rate, about six frames' worth of instructions. Checked through
+        // the cycle count rather than wall time, which a busy test machine stretches.
+        let frame = interpreter.cycles_per_frame() as u64;
+        assert!(interpreter.cycles() >= frame);
+        assert!(
+            interpreter.cycles() <= 7 * frame,
+            "{}",
+            interpreter.cycles()
+        );
+
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x00, 0xFD]);
+        assert_eq!(
+            interpreter.run_for(Duration::from_secs(10)),
+            RunOutcome::Exited
+        );
+    }
+
+    #[test]
+    fn step_unchecked_matches_step() {
+        let run = |unchecked: bool| {
+            let mut interpreter = Interpreter::for_test_rom();
+            interpreter.load_program(&RECURSIVE_CALL);
+            interpreter.patch(0x204, 0x1200).unwrap();
+            for _ in 0..50 {
+                let instruction = if unchecked {
+                    // SAFETY: the program only ever jumps around at 0x200.
+                    unsafe { interpreter.step_unchecked() }
+                } else {
+                    interpreter.step()
+                };
+                assert!(instruction.is_ok());
+            }
+            interpreter
+        };
+        let (checked, unchecked) = (run(false), run(true));
+        assert_eq!(checked.state_hash(), unchecked.state_hash());
+        assert_eq!(checked.memory, unchecked.memory);
+        assert_eq!(checked.cycles(), unchecked.cycles());
+    }
+
+    #[test]
+    fn step_unchecked_records_fault() {
+        let mut interpreter = Interpreter::new();
+        // A subroutine that calls itself until the stack overflows.
+        interpreter.load_program(&[0x22, 0x00]);
+        let fault = loop {
+            // SAFETY: `pc` stays at 0x200.
+            if let Err(fault) = unsafe { interpreter.step_unchecked() } {
+                break fault;
+            }
+        };
+        assert_eq!(fault, Fault::StackOverflow);
+        assert_eq!(interpreter.last_fault(), Some(&fault));
+    }
+
+    /// Run with `cargo test --release --features bench -- --nocapture step_unchecked_benchmark`.
+    #[cfg(feature = "bench")]
+    #[test]
+    fn step_unchecked_benchmark() {
+        const STEPS: usize = 10_000_000;
+        let mut interpreter = Interpreter::for_test_rom();
+        interpreter.load_program(&DRAW_CLEAR_LOOP);
+        let start = Instant::now();
+        for _ in 0..STEPS {
+            interpreter.step().unwrap();
+        }
+        let checked = start.elapsed();
+
+        let mut interpreter = Interpreter::for_test_rom();
+        interpreter.load_program(&DRAW_CLEAR_LOOP);
+        let start = Instant::now();
+        for _ in 0..STEPS {
+            // SAFETY: the loop stays between 0x200 and 0x207.
+            unsafe { interpreter.step_unchecked() }.unwrap();
+        }
+        let unchecked = start.elapsed();
+        println!("{STEPS} steps: step {checked:?}, step_unchecked {unchecked:?}");
+    }
+
+    #[test]
+    fn decode_table_matches_from_raw() {
+        for opcode in 0..=u16::MAX {
+            assert_eq!(
+                Instruction::from_raw_table(opcode),
+                Instruction::from_raw(opcode),
+                "{opcode:04X}"
+            );
+        }
+    }
+
+    /// Run with `cargo test --release --features bench -- --nocapture decode_table_benchmark`.
+    #[cfg(feature = "bench")]
+    #[test]
+    fn decode_table_benchmark() {
+        const SWEEPS: usize = 200;
+        let time = |decode: fn(u16) -> Instruction| {
+            let start = Instant::now();
+            for _ in 0..SWEEPS {
+                for opcode in 0..=u16::MAX {
+                    std::hint::black_box(decode(std::hint::black_box(opcode)));
+                }
+            }
+            start.elapsed()
+        };
+        let (matched, table) = (
+            time(Instruction::from_raw),
+            time(Instruction::from_raw_table),
+        );
+        println!(
+            "{SWEEPS} sweeps: from_raw {matched:?}, from_raw_table {table:?} ({:.1}x)",
+            table.as_secs_f64() / matched.as_secs_f64()
+        );
+    }
+
+    #[test]
+    fn emulated_time() {
+        for timing in [Timing::Flat, Timing::CycleCost] {
+            let mut interpreter = Interpreter::with_clock(
+                InterpreterConfig {
+                    clock_hz: 600,
+                    timing,
+                    ..Default::default()
+                },
+                Box::new(MockClock::new()),
+            );
+            interpreter.load_program(&[0x12, 0x00]);
+            assert_eq!(interpreter.emulated_time(), Duration::ZERO);
+
+            for _ in 0..120 {
+                assert_eq!(interpreter.tick_frame(), RunOutcome::Completed);
+            }
+            let error = interpreter.emulated_time().abs_diff(Duration::from_secs(2));
+            assert!(error < Duration::from_millis(1), "{timing:?}: {error:?}");
+        }
+    }
+
+    #[test]
+    fn step_result_reports_screen_changes() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x60, 0x01, 0xA0, 0x50, 0xD0, 0x05, 0x00, 0xE0]);
+
+        let result = interpreter.step_result().unwrap();
+        assert_eq!(result.instruction, Instruction::SetRegister(0, 1));
+        assert!(!result.screen_changed);
+        assert!(!interpreter.step_result().unwrap().screen_changed);
+
+        let result = interpreter.step_result().unwrap();
+        assert_eq!(result.instruction, Instruction::DrawSprite(0, 0, 5));
+        assert!(result.screen_changed);
+        assert!(interpreter.step_result().unwrap().screen_changed);
+    }
+
+    #[test]
+    fn beeper_stops_once_at_zero_crossing() {
+        struct MockBeeper(Arc<Mutex<Vec<bool>>>);
+        impl Beeper for MockBeeper {
+            fn set_playing(&mut self, playing: bool) {
+                self.0.lock().unwrap().push(playing);
+            }
+        }
+
+        // V0 = 3, sound timer = V0, loop
+        let clock = MockClock::new();
+        let mut interpreter =
+            Interpreter::with_clock(InterpreterConfig::default(), Box::new(clock.clone()));
+        interpreter.load_program(&[0x60, 0x03, 0xF0, 0x18, 0x12, 0x04]);
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        interpreter.set_beeper(MockBeeper(calls.clone()));
+
+        interpreter.tick_frame();
+        assert_eq!(*calls.lock().unwrap(), [true]);
+        for remaining in [2, 1] {
+            clock.advance(Duration::from_secs_f32(1.0 / 60.0));
+            interpreter.tick_frame();
+            assert_eq!(interpreter.timers.sound_timer, remaining);
+            assert_eq!(*calls.lock().unwrap(), [true]);
+        }
+        clock.advance(Duration::from_secs_f32(1.0 / 60.0));
+        interpreter.tick_frame();
+        assert_eq!(interpreter.timers.sound_timer, 0);
+        assert_eq!(*calls.lock().unwrap(), [true, false]);
+
+        for _ in 0..5 {
+            clock.advance(Duration::from_secs_f32(1.0 / 60.0));
+            interpreter.tick_frame();
+        }
+        assert_eq!(*calls.lock().unwrap(), [true, false]);
+    }
+
+    #[test]
+    fn reset_timers() {
+        let clock = MockClock::new();
+        let mut interpreter =
+            Interpreter::with_clock(InterpreterConfig::default(), Box::new(clock.clone()));
+        // V0 = 30, delay timer = V0, sound timer = V0, loop
+        interpreter.load_program(&[0x60, 0x1E, 0xF0, 0x15, 0xF0, 0x18, 0x12, 0x06]);
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        assert!(interpreter.beeping);
+
+        // Half a frame that would otherwise carry over into the next update.
+        clock.advance(Duration::from_secs_f32(0.5 / 60.0));
+        interpreter.timers.decrement_timers();
+        clock.advance(Duration::from_millis(100));
+        interpreter.reset_timers();
+        assert_eq!(interpreter.timers.delay_timer, 0);
+        assert_eq!(interpreter.timers.sound_timer, 0);
+        assert_eq!(interpreter.timers.rounding_remainder, 0.0);
+        assert_eq!(interpreter.timers.last_update, clock.now());
+        assert!(!interpreter.beeping);
+    }
+
+    #[test]
+    fn screen_starts_out_off() {
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            fill_pattern: 0xAA,
+            ..Default::default()
+        });
+        assert_eq!(interpreter.screen().len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+        assert!(interpreter.screen().iter().all(|pixel| *pixel == PIXEL_OFF));
+
+        interpreter.exec_raw(0x00FF).unwrap();
+        assert_eq!(interpreter.screen().len(), HIRES_WIDTH * HIRES_HEIGHT);
+        assert!(interpreter.screen().iter().all(|pixel| *pixel == PIXEL_OFF));
+    }
+
+    #[test]
+    fn snapshot_screen() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0xA0, 0x50, 0xD0, 0x05]);
+
+        let before = interpreter.snapshot_screen();
+        assert_eq!(interpreter.run_cycles(2), RunOutcome::Completed);
+        let after = interpreter.snapshot_screen();
+
+        assert!(before.iter().all(|pixel| *pixel == 0));
+        assert_ne!(before, after);
+        assert_eq!(after, interpreter.screen());
+    }
+
+    #[test]
+    fn frame_buffer_rgba() {
+        const ON: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+        const OFF: [u8; 4] = [0x00, 0x00, 0x00, 0xFF];
+        let mut interpreter = Interpreter::new();
+        interpreter.screen_buffer[0] = 1;
+        interpreter.screen_buffer[SCREEN_WIDTH + 1] = 2;
+
+        let rgba = interpreter.frame_buffer_rgba(ON, OFF);
+        assert_eq!(rgba.len(), SCREEN_WIDTH * SCREEN_HEIGHT * 4);
+        assert_eq!(rgba[0..4], ON);
+        assert_eq!(rgba[4..8], OFF);
+        let second_row = (SCREEN_WIDTH + 1) * 4;
+        assert_eq!(rgba[second_row..second_row + 4], ON);
+    }
+
+    #[test]
+    fn frame_buffer_rgba_transforms() {
+        const ON: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+        const OFF: [u8; 4] = [0x00, 0x00, 0x00, 0xFF];
+        let pixel = |rgba: &[u8], x: usize, y: usize| {
+            let index = (y * SCREEN_WIDTH + x) * 4;
+            rgba[index..index + 4].to_vec()
+        };
+        for (transform, x, y) in [
+            (DisplayTransform::Normal, 1, 2),
+            (DisplayTransform::FlipX, SCREEN_WIDTH - 2, 2),
+            (DisplayTransform::FlipY, 1, SCREEN_HEIGHT - 3),
+            (
+                DisplayTransform::Rotate180,
+                SCREEN_WIDTH - 2,
+                SCREEN_HEIGHT - 3,
+            ),
+        ] {
+            let mut interpreter = Interpreter::with_config(InterpreterConfig {
+                display_transform: transform,
+                ..Default::default()
+            });
+            interpreter.screen_buffer[2 * SCREEN_WIDTH + 1] = 1;
+
+            let rgba = interpreter.frame_buffer_rgba(ON, OFF);
+            assert_eq!(pixel(&rgba, x, y), ON, "{transform:?}");
+            assert_eq!(rgba.chunks(4).filter(|rgba| *rgba == ON).count(), 1);
+            assert_eq!(interpreter.screen()[2 * SCREEN_WIDTH + 1], 1);
+        }
+    }
+
+    // 0x200: wait for key into V0, 0x202: V1 += 1, 0x204: jump 0x200
+    const KEY_COUNTER: [u8; 6] = [0xF0, 0x0A, 0x71, 0x01, 0x12, 0x00];
+
+    #[test]
+    fn keys_past_f_are_ignored() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&KEY_COUNTER);
+        interpreter.set_key(16, true);
+        interpreter.push_key_event(KeyEvent::Down(16));
+        interpreter.push_key_event(KeyEvent::Up(0xFF));
+        assert_eq!(interpreter.run_cycles(10), RunOutcome::Completed);
+        assert_eq!(interpreter.keys_bitmask(), 0);
+        assert_eq!(interpreter.registers[1], 0);
+    }
+
+    #[test]
+    fn wait_for_key_ignores_key_repeat() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&KEY_COUNTER);
+
+        assert_eq!(interpreter.run_cycles(5), RunOutcome::Completed);
+        assert_eq!(interpreter.pc, PC_START_ADDRESS);
+
+        for _ in 0..10 {
+            interpreter.push_key_event(KeyEvent::Down(5));
+            assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        }
+        assert_eq!(interpreter.registers[0], 5);
+        assert_eq!(interpreter.registers[1], 1);
+
+        interpreter.push_key_event(KeyEvent::Up(5));
+        interpreter.push_key_event(KeyEvent::Down(5));
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[1], 2);
+    }
+
+    // 0x200: V0 = 5, 0x202: skip if key V0 is held, 0x204: V1 += 1, 0x206: V2 += 1,
+    // 0x208: skip if key V0 isn't held, 0x20A: V3 += 1, 0x20C: jump 0x20C
+    const KEY_SKIPS: [u8; 14] = [
+        0x60, 0x05, 0xE0, 0x9E, 0x71, 0x01, 0x72, 0x01, 0xE0, 0xA1, 0x73, 0x01, 0x12, 0x0C,
+    ];
+
+    #[test]
+    fn skip_if_key() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&KEY_SKIPS);
+        interpreter.set_key(5, true);
+        assert_eq!(interpreter.run_cycles(6), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[1..4], [0, 1, 1]);
+
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&KEY_SKIPS);
+        assert_eq!(interpreter.run_cycles(6), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[1..4], [1, 1, 0]);
+    }
+
+    #[test]
+    fn latched_keys_catch_short_taps() {
+        for (latch_keys, skipped) in [(false, 0), (true, 1)] {
+            let mut interpreter = Interpreter::with_clock(
+                InterpreterConfig {
+                    latch_keys,
+                    ..Default::default()
+                },
+                Box::new(MockClock::new()),
+            );
+            interpreter.load_program(&KEY_SKIPS);
+            interpreter.push_key_event(KeyEvent::Down(5));
+            interpreter.push_key_event(KeyEvent::Up(5));
+
+            assert_eq!(interpreter.tick_frame(), RunOutcome::Completed);
+            assert_eq!(
+                interpreter.registers[1],
+                1 - skipped,
+                "latch_keys: {latch_keys}"
+            );
+            // With latching, EXA1 sees the key held for the whole frame too.
+            assert_eq!(interpreter.registers[3], skipped);
+            assert_eq!(interpreter.keys_bitmask(), 0);
+            assert_eq!(interpreter.latched_keys, None);
+        }
+    }
+
+    #[test]
+    fn keys_bitmask() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&KEY_COUNTER);
+
+        interpreter.set_keys_bitmask(0b1000_0000_0010_0000);
+        assert_eq!(interpreter.keys_bitmask(), 0b1000_0000_0010_0000);
+        assert!(interpreter.keys[5] && interpreter.keys[0xF]);
+
+        // Both keys went down, FX0A picks the lowest
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[0], 5);
+        assert_eq!(interpreter.registers[1], 1);
+
+        // Holding the same keys isn't a new press
+        interpreter.set_keys_bitmask(0b1000_0000_0010_0000);
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[1], 1);
+
+        interpreter.set_keys_bitmask(0b1000_0000_0000_0001);
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[0], 0);
+        assert_eq!(interpreter.registers[1], 2);
+    }
+
+    #[test]
+    fn wait_for_key_with_key_repeat() {
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            ignore_key_repeat: false,
+            ..Default::default()
+        });
+        interpreter.load_program(&KEY_COUNTER);
+
+        for _ in 0..10 {
+            interpreter.push_key_event(KeyEvent::Down(5));
+            assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        }
+        assert_eq!(interpreter.registers[1], 10);
+    }
+
+    #[test]
+    fn replaying_recorded_input_reproduces_run() {
+        // Wait for a key into V0, put a random byte in V2, count in V1.
+        const PROGRAM: [u8; 8] = [0xF0, 0x0A, 0xC2, 0xFF, 0x71, 0x01, 0x12, 0x00];
+        let config = || InterpreterConfig {
+            ignore_key_repeat: false,
+            rng_seed: Some(7),
+            ..Default::default()
+        };
+
+        let mut recorded = Interpreter::with_clock(config(), Box::new(MockClock::new()));
+        recorded.load_program(&PROGRAM);
+        recorded.start_recording();
+        recorded.run_cycles(5);
+        recorded.set_key(3, true);
+        recorded.run_cycles(7);
+        recorded.push_key_event(KeyEvent::Down(3));
+        recorded.run_cycles(4);
+        recorded.set_key(3, false);
+        recorded.push_key_event(KeyEvent::Down(0xA));
+        recorded.run_cycles(9);
+        let recording = recorded.stop_recording().unwrap();
+        assert!(!recording.inputs().is_empty());
+
+        let mut replayed = Interpreter::with_clock(config(), Box::new(MockClock::new()));
+        replayed.load_program(&PROGRAM);
+        replayed.replay(&recording);
+        replayed.run_cycles(25);
+
+        assert!(!replayed.is_replaying());
+        assert_eq!(replayed.cycles, recorded.cycles);
+        assert_eq!(replayed.pc, recorded.pc);
+        assert_eq!(replayed.registers, recorded.registers);
+        assert_eq!(replayed.keys, recorded.keys);
+        assert_eq!(replayed.registers[0], 0xA);
+    }
+
+    #[test]
+    fn fade_buffer_decays_after_pixel_turns_off() {
+        let mut interpreter = Interpreter::with_clock(
+            InterpreterConfig {
+                fade_decay: Some(100),
+                ..Default::default()
+            },
+            Box::new(MockClock::new()),
+        );
+        interpreter.load_program(&[0x12, 0x00]);
+
+        interpreter.screen_buffer[0] = 1;
+        interpreter.tick_frame();
+        assert_eq!(interpreter.fade_buffer()[0], 255);
+
+        interpreter.screen_buffer[0] = 0;
+        interpreter.tick_frame();
+        assert_eq!(interpreter.fade_buffer()[0], 155);
+        interpreter.tick_frame();
+        assert_eq!(interpreter.fade_buffer()[0], 55);
+        interpreter.tick_frame();
+        assert_eq!(interpreter.fade_buffer()[0], 0);
+    }
+
+    #[test]
+    fn custom_handler() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&UNKNOWN_OPCODE);
+        // Treat 0xFFFF as "VE = 0xEE"
+        interpreter.set_custom_handler(|interpreter, opcode| {
+            if opcode != 0xFFFF {
+                return false;
+            }
+            interpreter.set_v(0xE, 0xEE).unwrap();
+            true
+        });
+
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[0xE], 0xEE);
+        assert_eq!(interpreter.registers[1], 2);
+    }
+
+    #[test]
+    fn decode_fault_reports_address_and_opcode() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&UNKNOWN_OPCODE);
+
+        let RunOutcome::Fault(fault) = interpreter.run_cycles(3) else {
+            panic!("the unknown opcode didn't fault");
+        };
+        assert_eq!(fault.pc(), Some(0x202));
+        assert!(matches!(fault, Fault::DecodeError { opcode: 0xFFFF, .. }));
+        assert_eq!(fault.to_string(), "unknown opcode 0xFFFF at 0x202");
+    }
+
+    #[test]
+    fn custom_handler_falls_back_to_policy() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&UNKNOWN_OPCODE);
+        interpreter.set_custom_handler(|_, _| false);
+
+        assert_eq!(
+            interpreter.run_cycles(3),
+            RunOutcome::Fault(Fault::DecodeError {
+                pc: PC_START_ADDRESS + 2,
+                opcode: 0xFFFF
+            })
+        );
+    }
+
+    #[test]
+    fn load_audio_pattern() {
+        let pattern: Vec<u8> = (0..16).map(|byte| byte * 0x11).collect();
+        let mut interpreter = Interpreter::new();
+        // I = 0x300, load pattern
+        interpreter.load_program(&[0xA3, 0x00, 0xF0, 0x02]);
+        interpreter.set_memory(0x300, &pattern).unwrap();
+        assert_eq!(interpreter.audio_pattern(), &[0; 16]);
+
+        assert_eq!(interpreter.run_cycles(2), RunOutcome::Completed);
+        assert_eq!(interpreter.audio_pattern()[..], pattern);
+        assert_eq!(interpreter.i, 0x300);
+    }
+
+    #[test]
+    fn set_pitch() {
+        // V3 = 112, pitch = V3, sound timer = V3
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x63, 0x70, 0xF3, 0x3A, 0xF3, 0x18]);
+        assert_eq!(interpreter.pitch(), DEFAULT_PITCH);
+
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        assert_eq!(interpreter.pitch(), 112);
+        assert_eq!(interpreter.pattern_samples(48000).len(), 48000 * 112 / 60);
+    }
+
+    #[test]
+    fn beep_samples_follow_sound_timer() {
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter
+            .beep_samples(48000, 440.0, Waveform::Square)
+            .is_empty());
+
+        interpreter.timers.sound_timer = 6;
+        let samples = interpreter.beep_samples(48000, 440.0, Waveform::Square);
+        assert_eq!(samples.len(), 4800);
+    }
+
+    #[test]
+    fn pc_out_of_bounds() {
+        let mut interpreter = Interpreter::new();
+        interpreter.pc = MEMORY_SIZE - 1;
+        assert_eq!(
+            interpreter.step(),
+            Err(Fault::PcOutOfBounds(MEMORY_SIZE - 1))
+        );
+    }
+
+    #[test]
+    fn pc_at_top_of_memory_faults_without_wrapping() {
+        let mut interpreter = Interpreter::new();
+        interpreter.memory[MEMORY_SIZE - 2..].copy_from_slice(&[0x60, 0x05]);
+        interpreter.pc = MEMORY_SIZE - 2;
+
+        assert_eq!(interpreter.step(), Ok(Instruction::SetRegister(0, 5)));
+        assert_eq!(interpreter.pc, MEMORY_SIZE);
+        assert_eq!(interpreter.step(), Err(Fault::PcOutOfBounds(MEMORY_SIZE)));
+    }
+
+    #[test]
+    fn pc_at_top_of_memory_wraps() {
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            i_wraps: true,
+            ..Default::default()
+        });
+        interpreter.memory[MEMORY_SIZE - 2..].copy_from_slice(&[0x60, 0x05]);
+        interpreter.memory[..2].copy_from_slice(&[0x61, 0x06]);
+        interpreter.pc = MEMORY_SIZE - 2;
+
+        assert_eq!(interpreter.step(), Ok(Instruction::SetRegister(0, 5)));
+        assert_eq!(interpreter.pc, 0);
+        assert_eq!(interpreter.step(), Ok(Instruction::SetRegister(1, 6)));
+
+        // A word straddling the end of memory is read across the wrap
+        interpreter.memory[MEMORY_SIZE - 1] = 0x62;
+        interpreter.memory[0] = 0x07;
+        interpreter.pc = MEMORY_SIZE - 1;
+        assert_eq!(interpreter.step(), Ok(Instruction::SetRegister(2, 7)));
+        assert_eq!(interpreter.pc, 1);
+    }
+
+    #[test]
+    fn memory_out_of_bounds() {
+        let mut interpreter = Interpreter::new();
+        // I = 0xFFF, store V0..V1
+        interpreter.load_program(&[0xAF, 0xFF, 0xF1, 0x55]);
+        assert_eq!(
+            interpreter.run_cycles(2),
+            RunOutcome::Fault(Fault::MemoryOutOfBounds(0x1000))
+        );
+    }
+
+    // V0 = 0, I = 0xFFE, draw 4 rows from 0xFFE..=0x001
+    const DRAW_AT_TOP_OF_MEMORY: [u8; 6] = [0x60, 0x00, 0xAF, 0xFE, 0xD0, 0x04];
+
+    #[test]
+    fn i_wraps_around_memory() {
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            i_wraps: true,
+            ..Default::default()
+        });
+        interpreter.load_program(&DRAW_AT_TOP_OF_MEMORY);
+        interpreter.memory[0xFFE] = 0x80;
+        interpreter.memory[0xFFF] = 0x40;
+        interpreter.memory[0x000] = 0x20;
+        interpreter.memory[0x001] = 0x10;
+
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        for row in 0..4 {
+            assert_eq!(interpreter.screen_buffer[row * SCREEN_WIDTH + row], 1);
+        }
+    }
+
+    #[test]
+    fn i_faults_past_memory_without_wrapping() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&DRAW_AT_TOP_OF_MEMORY);
+
+        assert_eq!(
+            interpreter.run_cycles(3),
+            RunOutcome::Fault(Fault::MemoryOutOfBounds(0x1000))
+        );
+    }
+
+    #[test]
+    fn protect_reserved_memory() {
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            protect_reserved: true,
+            ..Default::default()
+        });
+        // I = 0x1FF, store V0
+        interpreter.load_program(&[0xA1, 0xFF, 0xF0, 0x55]);
+        assert_eq!(
+            interpreter.run_cycles(2),
+            RunOutcome::Fault(Fault::WriteToReserved(0x1FF))
+        );
+    }
+
+    #[test]
+    fn protect_reserved_jumps() {
+        for opcode in [[0x10, 0x60], [0x20, 0x60]] {
+            let mut interpreter = Interpreter::with_config(InterpreterConfig {
+                protect_reserved_jumps: true,
+                ..Default::default()
+            });
+            interpreter.load_program(&opcode);
+            assert_eq!(
+                interpreter.run_cycles(1),
+                RunOutcome::Fault(Fault::JumpToReserved {
+                    pc: 0x200,
+                    target: 0x60
+                })
+            );
+        }
+
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x10, 0x60]);
+        assert_eq!(interpreter.run_cycles(1), RunOutcome::Completed);
+        assert_eq!(interpreter.pc, 0x60);
+    }
+
+    #[test]
+    fn fault_messages() {
+        let messages = [
+            (
+                Fault::PcOutOfBounds(0x1000),
+                "program counter 0x1000 is outside of memory",
+            ),
+            (
+                Fault::MemoryOutOfBounds(0x1001),
+                "memory access at 0x1001 is outside of memory",
+            ),
+            (
+                Fault::WriteToReserved(0x50),
+                "write to reserved memory at 0x050",
+            ),
+            (
+                Fault::JumpToReserved {
+                    pc: 0x200,
+                    target: 0x60,
+                },
+                "jump from 0x200 into reserved memory at 0x060",
+            ),
+            (
+                Fault::DecodeError {
+                    pc: 0x202,
+                    opcode: 0xFFFF,
+                },
+                "unknown opcode 0xFFFF at 0x202",
+            ),
+            (
+                Fault::Unsupported {
+                    pc: 0x300,
+                    instruction: Instruction::ScrollLeft,
+                },
+                "unsupported instruction SCL at 0x300",
+            ),
+            (Fault::InvalidRegister(16), "register V16 doesn't exist"),
+            (Fault::MisalignedPc(0x203), "program counter 0x203 is odd"),
+            (Fault::StackOverflow, "stack overflow"),
+            (Fault::StackUnderflow, "return with an empty stack"),
+        ];
+        for (fault, message) in messages {
+            assert_eq!(fault.to_string(), message);
+        }
+
+        let error: Box<dyn Error> = Box::new(Fault::StackOverflow);
+        assert_eq!(error.to_string(), "stack overflow");
+    }
+
+    #[test]
+    fn run_until_pc() {
+        // 0x200: call 0x206, 0x202: jump 0x202, 0x206: V0 += 1, 0x208: V0 += 1, 0x20A: return
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[
+            0x22, 0x06, 0x12, 0x02, 0x00, 0x00, 0x70, 0x01, 0x70, 0x01, 0x00, 0xEE,
+        ]);
+
+        assert_eq!(interpreter.run_until_pc(0x202, 100), RunOutcome::PcReached);
+        assert_eq!(interpreter.registers[0], 2);
+        assert_eq!(interpreter.run_until_pc(0x202, 0), RunOutcome::PcReached);
+        assert_eq!(interpreter.run_until_pc(0x300, 10), RunOutcome::Completed);
+    }
+
+    // 0x200: call 0x206, 0x202: V1 = 1, 0x204: jump 0x204,
+    // 0x206: V0 += 1, 0x208: skip if V0 == 3, 0x20A: call 0x206, 0x20C: return
+    const RECURSIVE_CALL: [u8; 14] = [
+        0x22, 0x06, 0x61, 0x01, 0x12, 0x04, 0x70, 0x01, 0x30, 0x03, 0x22, 0x06, 0x00, 0xEE,
+    ];
+
+    #[test]
+    fn step_over() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&RECURSIVE_CALL);
+
+        assert_eq!(interpreter.step_over(100), RunOutcome::PcReached);
+        assert_eq!(interpreter.pc, 0x202);
+        assert_eq!(interpreter.registers[..2], [3, 0]);
+
+        assert_eq!(interpreter.step_over(100), RunOutcome::Completed);
+        assert_eq!(interpreter.pc, 0x204);
+        assert_eq!(interpreter.registers[1], 1);
+
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&RECURSIVE_CALL);
+        assert_eq!(interpreter.step_over(4), RunOutcome::Completed);
+        assert_eq!(interpreter.stack.position, 2);
+    }
+
+    #[test]
+    fn step_out() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&RECURSIVE_CALL);
+        assert_eq!(interpreter.step_out(100), RunOutcome::Completed);
+        assert_eq!(interpreter.pc, PC_START_ADDRESS);
+
+        // Two levels deep, at 0x206
+        assert_eq!(interpreter.run_cycles(4), RunOutcome::Completed);
+        assert_eq!((interpreter.pc, interpreter.stack.position), (0x206, 2));
+
+        assert_eq!(interpreter.step_out(100), RunOutcome::PcReached);
+        assert_eq!((interpreter.pc, interpreter.stack.position), (0x20C, 1));
+        assert_eq!(interpreter.registers[0], 3);
+        assert_eq!(interpreter.step_out(100), RunOutcome::PcReached);
+        assert_eq!((interpreter.pc, interpreter.stack.position), (0x202, 0));
+    }
+
+    // 0x200: V0 += 1, 0x202: V1 += 1, 0x204: I = 0x300, 0x206: store V0..V1, 0x208: jump 0x200
+    const COUNTER: [u8; 10] = [0x70, 0x01, 0x71, 0x01, 0xA3, 0x00, 0xF1, 0x55, 0x12, 0x00];
+
+    #[test]
+    fn register_watchpoint() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&COUNTER);
+        interpreter.add_watchpoint(WatchTarget::Register(1));
+
+        assert_eq!(
+            interpreter.run_cycles(100),
+            RunOutcome::WatchpointHit(WatchTarget::Register(1))
+        );
+        assert_eq!(interpreter.pc, 0x204);
+        assert_eq!(interpreter.registers[1], 1);
+
+        // Steps that leave V1 alone don't stop, and neither does set_v.
+        interpreter.set_v(1, 5).unwrap();
+        assert_eq!(interpreter.run_cycles(4), RunOutcome::Completed);
+        assert_eq!(interpreter.pc, 0x202);
+
+        interpreter.remove_watchpoint(WatchTarget::Register(1));
+        assert_eq!(interpreter.run_cycles(100), RunOutcome::Completed);
+    }
+
+    #[test]
+    fn memory_watchpoint() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&COUNTER);
+        interpreter.add_watchpoint(WatchTarget::Memory(0x301));
+
+        assert_eq!(
+            interpreter.run_cycles(100),
+            RunOutcome::WatchpointHit(WatchTarget::Memory(0x301))
+        );
+        assert_eq!(interpreter.pc, 0x208);
+        assert_eq!(interpreter.memory[0x300..0x302], [1, 1]);
+        assert_eq!(
+            interpreter.run_cycles(100),
+            RunOutcome::WatchpointHit(WatchTarget::Memory(0x301))
+        );
+        assert_eq!(interpreter.memory[0x301], 2);
+    }
+
+    #[test]
+    fn registers_slice_round_trip() {
+        let data = [1, 2, 3, 4, 5, 6, 7, 8];
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.registers_from_slice(&data), Ok(()));
+        assert_eq!(interpreter.registers_to_slice(8), Ok(&data[..]));
+        assert_eq!(interpreter.v(8), Some(0));
+
+        let mut other = Interpreter::new();
+        assert_eq!(
+            other.registers_from_slice(interpreter.registers_to_slice(8).unwrap()),
+            Ok(())
+        );
+        assert_eq!(other.registers, interpreter.registers);
+    }
+
+    #[test]
+    fn registers_slice_past_vf() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.registers_to_slice(16).map(<[u8]>::len), Ok(16));
+        assert_eq!(
+            interpreter.registers_to_slice(17),
+            Err(Fault::InvalidRegister(16))
+        );
+        assert_eq!(
+            interpreter.registers_from_slice(&[1; 17]),
+            Err(Fault::InvalidRegister(16))
+        );
+        assert_eq!(interpreter.registers, [0; 16]);
+    }
+
+    #[test]
+    fn skip_if_equal() {
+        // V0 = 1, skip if V0 == 1, V1 = 1, skip if V0 != 1, V2 = 1
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x60, 0x01, 0x30, 0x01, 0x61, 0x01, 0x40, 0x01, 0x62, 0x01]);
+
+        assert_eq!(interpreter.run_cycles(4), RunOutcome::Completed);
+        assert_eq!(interpreter.registers[1..3], [0, 1]);
+    }
+
+    #[test]
+    fn random_is_masked_and_seeded() {
+        let program = [0xC0, 0x0F, 0xC1, 0xFF];
+        let run = || {
+            let mut interpreter = Interpreter::for_test_rom();
+            interpreter.load_program(&program);
+            assert_eq!(interpreter.run_cycles(2), RunOutcome::Completed);
+            interpreter.registers
+        };
+
+        let registers = run();
+        assert!(registers[0] <= 0x0F);
+        assert_eq!(registers, run());
+    }
+
+    #[test]
+    fn quirk_test_rom_passes() {
+        // A hand-written stand-in for the community quirk test ROM, which isn't bundled. It
+        // probes FX55/FX65 incrementing I and BNNN adding V0, and draws glyph 1 if the
+        // interpreter behaves like a COSMAC VIP and glyph 0 otherwise.
+        const QUIRK_TEST: [u8; 36] = [
+            0x60, 0xAB, // V0 = 0xAB
+            0xA3, 0x00, // I = 0x300
+            0xF0, 0x55, // store V0, I moves on to 0x301
+            0x60, 0xCD, // V0 = 0xCD
+            0xF0, 0x55, // store V0 at 0x301, or over 0xAB without the quirk
+            0xA3, 0x00, // I = 0x300
+            0xF0, 0x65, // load V0
+            0xA0, 0x50, // I = glyph 0
+            0x30, 0xAB, // skip if V0 == 0xAB
+            0x12, 0x1E, // jump to draw
+            0x60, 0x02, // V0 = 2
+            0x62, 0x00, // V2 = 0
+            0xB2, 0x1A, // jump to 0x21C with V0, or to 0x21A with V2
+            0x12, 0x1E, // jump to draw
+            0xA0, 0x55, // I = glyph 1
+            0x61, 0x00, // V1 = 0
+            0xD1, 0x15, // draw
+            0x12, 0x22, // jump here forever
+        ];
+        let mut interpreter = Interpreter::for_test_rom();
+        interpreter.load_program(&QUIRK_TEST);
+
+        assert_eq!(interpreter.run_cycles(100), RunOutcome::Completed);
+        assert_screen(
+            &interpreter,
+            "
+            ..#.
+            .##.
+            ..#.
+            ..#.
+            .###
+            ",
+        );
+
+        // Only the jump quirk differs, so this fails on the BNNN probe.
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            quirks: Quirks {
+                jump_uses_vx: true,
+                ..Quirks::cosmac_vip()
+            },
+            ..Default::default()
+        });
+        interpreter.load_program(&QUIRK_TEST);
+        assert_eq!(interpreter.run_cycles(100), RunOutcome::Completed);
+        assert_screen(
+            &interpreter,
+            "
+            ####
+            #..#
+            #..#
+            #..#
+            ####
+            ",
+        );
+    }
+
+    #[test]
+    fn instruction_display() {
+        assert_eq!(Instruction::ClearScreen.to_string(), "CLS");
+        assert_eq!(Instruction::Jump(0x2A0).to_string(), "JP 0x2A0");
+        assert_eq!(Instruction::SetRegister(0xB, 5).to_string(), "LD VB, 0x05");
+        assert_eq!(
+            Instruction::DrawSprite(0, 1, 5).to_string(),
+            "DRW V0, V1, 5"
+        );
+        assert_eq!(Instruction::StoreRegisters(3).to_string(), "LD [I], V3");
+    }
+
+    #[test]
+    fn unknown_keeps_raw_word() {
+        let instruction = Instruction::from_raw(0x5AB1);
+        assert_eq!(instruction, Instruction::Unknown(0x5AB1));
+        assert_eq!(instruction.to_string(), "DB 0x5AB1");
+        assert_eq!(Instruction::from_raw(0xF0FF).to_string(), "DB 0xF0FF");
+    }
+
+    #[test]
+    fn disassembly_labels() {
+        let mut disassembler = Disassembler::new();
+        disassembler.add_label(0x204, "loop");
+        let listing = disassembler.disassemble(&[0xA0, 0x55, 0x60, 0x01, 0x12, 0x04]);
+
+        assert_eq!(
+            listing,
+            "entry:\n\
+             0x200  A055  LD I, 0x055  ; font_1\n\
+             0x202  6001  LD V0, 0x01\n\
+             loop:\n\
+             0x204  1204  JP 0x204  ; loop\n"
+        );
+    }
+
+    #[test]
+    fn write_disassembly() {
+        let mut out = Vec::new();
+        Disassembler::new()
+            .write_disassembly(&[0x00, 0xE0, 0x81, 0x26, 0x12, 0x00], &mut out)
+            .unwrap();
+
+        let listing = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = listing.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[1], "0x200  00E0  CLS");
+        assert_eq!(lines[3], "0x204  1200  JP 0x200  ; entry");
+    }
+
+    #[test]
+    fn decode_super_chip_instructions() {
+        for (opcode, instruction) in [
+            (0x00C3, Instruction::ScrollDown(3)),
+            (0x00FB, Instruction::ScrollRight),
+            (0x00FC, Instruction::ScrollLeft),
+            (0x00FD, Instruction::Exit),
+            (0x00FE, Instruction::LowRes),
+            (0x00FF, Instruction::HighRes),
+            (0xD120, Instruction::DrawLargeSprite(1, 2)),
+            (0xF430, Instruction::LargeFontCharacter(4)),
+            (0xF575, Instruction::StoreFlags(5)),
+            (0xF685, Instruction::LoadFlags(6)),
+        ] {
+            assert_eq!(Instruction::from_raw(opcode), instruction, "{opcode:#06X}");
+        }
+        assert_eq!(
+            Instruction::from_raw(0xD121),
+            Instruction::DrawSprite(1, 2, 1)
+        );
+    }
+
+    #[test]
+    fn decode_only_super_chip_instructions_are_unsupported() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0xF5, 0x75]);
+        assert_eq!(
+            interpreter.run_cycles(1),
+            RunOutcome::Fault(Fault::Unsupported {
+                pc: 0x200,
+                instruction: Instruction::StoreFlags(5)
+            })
+        );
+    }
+
+    #[test]
+    fn decode_00xx_instructions() {
+        assert_eq!(Instruction::from_raw(0x00C4), Instruction::ScrollDown(4));
+        assert_eq!(Instruction::from_raw(0x00CF), Instruction::ScrollDown(0xF));
+        assert_eq!(Instruction::from_raw(0x00E0), Instruction::ClearScreen);
+        assert_eq!(Instruction::from_raw(0x00EE), Instruction::Return);
+        assert_eq!(Instruction::from_raw(0x00FB), Instruction::ScrollRight);
+        assert_eq!(Instruction::from_raw(0x00FC), Instruction::ScrollLeft);
+        assert_eq!(Instruction::from_raw(0x00FD), Instruction::Exit);
+        assert_eq!(Instruction::from_raw(0x00FE), Instruction::LowRes);
+        assert_eq!(Instruction::from_raw(0x00FF), Instruction::HighRes);
+
+        assert_eq!(Instruction::from_raw(0x00E1), Instruction::Unknown(0x00E1));
+        assert_eq!(Instruction::from_raw(0x01E0), Instruction::Unknown(0x01E0));
+        assert_eq!(Instruction::from_raw(0x0000), Instruction::Unknown(0x0000));
+    }
+
+    #[test]
+    fn exit_stops_running() {
+        // V0 = 1, exit, V0 = 2
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x60, 0x01, 0x00, 0xFD, 0x60, 0x02]);
+
+        assert_eq!(interpreter.run_cycles(10), RunOutcome::Exited);
+        assert_eq!(interpreter.registers[0], 1);
+        assert_eq!(interpreter.pc, 0x202);
+        assert_eq!(interpreter.run(), RunOutcome::Exited);
+        assert_eq!(interpreter.registers[0], 1);
+    }
+
+    #[test]
+    fn instruction_operands() {
+        let draw = Instruction::DrawSprite(1, 2, 3);
+        assert_eq!(draw.vx(), Some(1));
+        assert_eq!(draw.vy(), Some(2));
+        assert_eq!(draw.n(), Some(3));
+        assert_eq!(draw.nn(), None);
+        assert_eq!(draw.nnn(), None);
+
+        let add = Instruction::AddToRegister(0xA, 0xFF);
+        assert_eq!(add.vx(), Some(0xA));
+        assert_eq!(add.vy(), None);
+        assert_eq!(add.nn(), Some(0xFF));
+
+        assert_eq!(Instruction::SetI(0x123).nnn(), Some(0x123));
+        assert_eq!(Instruction::Call(0x456).nnn(), Some(0x456));
+        assert_eq!(Instruction::ClearScreen.vx(), None);
+    }
+
+    #[test]
+    fn frame_counter_and_callback() {
+        let clock = MockClock::new();
+        let mut interpreter =
+            Interpreter::with_clock(InterpreterConfig::default(), Box::new(clock.clone()));
+        interpreter.load_program(&[0x12, 0x00]);
+        let callbacks = Arc::new(AtomicU64::new(0));
+        let counter = callbacks.clone();
+        interpreter.set_on_frame(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        for _ in 0..60 {
+            clock.advance(Duration::from_secs(1) / 60);
+            interpreter.tick_frame();
+        }
+        assert!(interpreter.frames().abs_diff(60) <= 1);
+        assert_eq!(callbacks.load(Ordering::Relaxed), interpreter.frames());
+
+        // A stall delivers every missed frame
+        clock.advance(Duration::from_secs(1));
+        interpreter.tick_frame();
+        assert!(interpreter.frames().abs_diff(120) <= 1);
+        assert_eq!(callbacks.load(Ordering::Relaxed), interpreter.frames());
+    }
+
+    #[test]
+    fn builder() {
+        let clock = MockClock::new();
+        let mut interpreter = Interpreter::builder()
+            .rom(&[0xC0, 0xFF])
+            .clock_hz(1200)
+            .quirks(Quirks::super_chip())
+            .seed(42)
+            .clock(Box::new(clock.clone()))
+            .build();
+
+        assert_eq!(interpreter.program_bytes(), [0xC0, 0xFF]);
+        assert_eq!(interpreter.config.clock_hz, 1200);
+        assert_eq!(interpreter.cycles_per_frame(), 20);
+        assert_eq!(interpreter.config.quirks, Quirks::super_chip());
+        assert_eq!(interpreter.config.rng_seed, Some(42));
+
+        let mut seeded = Interpreter::builder().rom(&[0xC0, 0xFF]).seed(42).build();
+        interpreter.step().unwrap();
+        seeded.step().unwrap();
+        assert_eq!(interpreter.registers[0], seeded.registers[0]);
+
+        interpreter.timers.delay_timer = 10;
+        clock.advance(Duration::from_secs(1) / 10);
+        interpreter.timers.decrement_timers();
+        assert_eq!(interpreter.timers.delay_timer, 4);
+    }
+
+    #[test]
+    fn font_character() {
+        let mut interpreter = Interpreter::new();
+        // V0 = 0xA, I = glyph A
+        interpreter.load_program(&[0x60, 0x0A, 0xF0, 0x29]);
+
+        assert_eq!(interpreter.run_cycles(2), RunOutcome::Completed);
+        assert_eq!(interpreter.i, FONT_START_ADDRESS + 0xA * 5);
+    }
+
+    #[test]
+    fn relocated_font() {
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            font_address: 0x100,
+            ..Default::default()
+        });
+        interpreter.load_program(&[0x60, 0x07, 0xF0, 0x29]);
+
+        assert_eq!(interpreter.memory[0x100..0x100 + FONT.len()], FONT);
+        assert!(
+            interpreter.memory[FONT_START_ADDRESS..FONT_START_ADDRESS + 5]
+                .iter()
+                .all(|byte| *byte == 0)
+        );
+        assert_eq!(interpreter.run_cycles(2), RunOutcome::Completed);
+        assert_eq!(interpreter.i, 0x100 + 7 * 5);
+        assert_eq!(
+            interpreter.memory[interpreter.i..interpreter.i + 5],
+            FONT[35..40]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "the font must fit")]
+    fn font_address_is_validated() {
+        Interpreter::with_config(InterpreterConfig {
+            font_address: 0x1D0,
+            ..Default::default()
+        });
+    }
+
+    // 0x200: I = glyph 0, 0x202: draw, 0x204: clear, 0x206: jump 0x202
+    const DRAW_CLEAR_LOOP: [u8; 8] = [0xA0, 0x50, 0xD0, 0x05, 0x00, 0xE0, 0x12, 0x02];
+
+    #[test]
+    fn flicker_detection() {
+        let mut interpreter = Interpreter::with_clock(
+            InterpreterConfig {
+                flicker_window: Some(4),
+                ..Default::default()
+            },
+            Box::new(MockClock::new()),
+        );
+        interpreter.load_program(&DRAW_CLEAR_LOOP);
+
+        assert_eq!(interpreter.run_cycles(30), RunOutcome::Completed);
+        assert_eq!(interpreter.cycles(), 30);
+        assert_eq!(
+            interpreter.diagnostics(),
+            [Diagnostic::DrawThenClear {
+                draw_pc: 0x202,
+                clear_pc: 0x204
+            }]
+        );
+    }
+
+    #[test]
+    fn flicker_detection_is_off_by_default() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&DRAW_CLEAR_LOOP);
+
+        assert_eq!(interpreter.run_cycles(30), RunOutcome::Completed);
+        assert!(interpreter.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn stack_imbalance_detection() {
+        // 0x200: call 0x204, 0x202: jump 0x200, 0x204: jump 0x200 instead of returning
+        const LEAKY_CALL: [u8; 6] = [0x22, 0x04, 0x12, 0x00, 0x12, 0x00];
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            stack_imbalance: Some((16, 3)),
+            ..Default::default()
+        });
+        interpreter.load_program(&LEAKY_CALL);
+
+        assert_eq!(interpreter.run_cycles(6), RunOutcome::Completed);
+        assert!(interpreter.diagnostics().is_empty());
+        assert_eq!(interpreter.run_cycles(2), RunOutcome::Completed);
+        assert_eq!(
+            interpreter.diagnostics(),
+            [Diagnostic::StackImbalance {
+                pc: 0x200,
+                delta: 4
+            }]
+        );
+
+        // Balanced calls never trip it.
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            stack_imbalance: Some((16, 1)),
+            ..Default::default()
+        });
+        // 0x200: call 0x204, 0x202: jump 0x200, 0x204: return
+        interpreter.load_program(&[0x22, 0x04, 0x12, 0x00, 0x00, 0xEE]);
+        assert_eq!(interpreter.run_cycles(60), RunOutcome::Completed);
+        assert!(interpreter.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn unset_i_detection() {
+        // draw, I = glyph 0, draw
+        const PROGRAM: [u8; 6] = [0xD0, 0x05, 0xA0, 0x50, 0xD0, 0x05];
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            check_unset_i: true,
+            ..Default::default()
+        });
+        interpreter.load_program(&PROGRAM);
+
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        assert_eq!(
+            interpreter.diagnostics(),
+            [Diagnostic::UnsetI { pc: 0x200 }]
+        );
+
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&PROGRAM);
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        assert!(interpreter.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn add_to_i_sets_i() {
+        // V0 = 0x10, I += V0, load V0
+        const PROGRAM: [u8; 6] = [0x60, 0x10, 0xF0, 0x1E, 0xF0, 0x65];
+        let mut interpreter = Interpreter::with_config(InterpreterConfig {
+            check_unset_i: true,
+            ..Default::default()
+        });
+        interpreter.load_program(&PROGRAM);
+
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        assert_eq!(interpreter.i, 0x11);
+        assert!(interpreter.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn add_to_i_wraps_and_leaves_vf() {
+        let mut interpreter = Interpreter::new();
+        interpreter.i = 0xFFFF;
+        interpreter.registers[0x3] = 0x02;
+        interpreter.registers[0xF] = 0x55;
+        interpreter.execute(Instruction::from_raw(0xF31E)).unwrap();
+        assert_eq!(interpreter.i, 0x0001);
+        assert_eq!(interpreter.registers[0xF], 0x55);
+        assert_eq!(Instruction::AddToI(3).to_string(), "ADD I, V3");
+    }
+
+    #[test]
+    fn random_programs_dont_panic() {
+        for seed in 1..=64 {
+            let mut rng = Rng::new(seed);
+            let rom: Vec<u8> = (0..MEMORY_SIZE - PC_START_ADDRESS)
+                .map(|_| rng.next_u8())
+                .collect();
+            let quirks = if seed % 2 == 0 {
+                Quirks::xo_chip()
+            } else {
+                Quirks::cosmac_vip()
+            };
+            let config = InterpreterConfig {
+                quirks,
+                rng_seed: Some(seed),
+                i_wraps: seed % 4 < 2,
+                unimplemented_policy: if seed % 3 == 0 {
+                    UnimplementedPolicy::Fault
+                } else {
+                    UnimplementedPolicy::Skip
+                },
+                timing: if seed % 5 < 2 {
+                    Timing::CycleCost
+                } else {
+                    Timing::Flat
+                },
+                clock_hz: 1 + rng.next_u8() as u32 * 8,
+                timer_hz: 1 + rng.next_u8() as u32 % 120,
+                ..Default::default()
+            };
+            let mut interpreter = Interpreter::with_clock(config, Box::new(MockClock::new()));
+            interpreter.load_program(&rom);
+
+            for round in 0..20 {
+                let outcome = if round % 2 == 0 {
+                    interpreter.run_cycles(500)
+                } else {
+                    interpreter.tick_frame()
+                };
+                if let RunOutcome::Fault(_) = outcome {
+                    // Carry on somewhere else in the program
+                    let offset = (rng.next_u8() as usize) << 3;
+                    interpreter.pc = PC_START_ADDRESS + offset;
+                }
+                // Keys past 0xF included, as front-ends may pass anything through.
+                let key = rng.next_u8();
+                interpreter.push_key_event(KeyEvent::Down(key));
+                interpreter.push_key_event(KeyEvent::Up(rng.next_u8()));
+                interpreter.set_key(key as usize, rng.next_u8() & 1 != 0);
+            }
+        }
+    }
+
+    #[test]
+    fn random_bundles_dont_panic() {
+        for seed in 1..=256 {
+            let mut rng = Rng::new(seed);
+            let value: Vec<u8> = (0..rng.next_u8() % 16).map(|_| rng.next_u8()).collect();
+            let value = String::from_utf8_lossy(&value);
+            for key in ["clock_hz", "quirks.clip_x", "rom"] {
+                let _ = Interpreter::load_bundle(&format!("chip8-bundle\nrom=00\n{key}={value}\n"));
+            }
+        }
+    }
+
+    #[test]
+    fn cycle_cost_timing() {
+        // V0 = 1, V1 = 2, jump 0x200: cheap instructions
+        let program = [0x60, 0x01, 0x61, 0x02, 0x12, 0x00];
+        let throughput = |timing| {
+            let mut interpreter = Interpreter::builder()
+                .rom(&program)
+                .clock(Box::new(MockClock::new()))
+                .build();
+            interpreter.config.timing = timing;
+            assert_eq!(interpreter.tick_frame(), RunOutcome::Completed);
+            interpreter.cycles()
+        };
+
+        assert_eq!(throughput(Timing::Flat), 12);
+        // Two 6 cycle loads and a 12 cycle jump per 24 machine cycles
+        assert_eq!(
+            throughput(Timing::CycleCost),
+            (MACHINE_CYCLES_PER_SECOND as u64 / 60).div_ceil(24) * 3
+        );
+    }
+
+    #[test]
+    fn nibble() {
+        let yummy = 0x1234;
+        assert_eq!(Instruction::nibble_left(yummy, 0), 1);
+        assert_eq!(Instruction::nibble_left(yummy, 1), 2);
+        assert_eq!(Instruction::nibble_left(yummy, 2), 3);
+        assert_eq!(Instruction::nibble_left(yummy, 3), 4);
+    }
+
+    #[test]
+    fn nibble_right() {
+        let yummy = 0x1234;
+        assert_eq!(Instruction::nibble_right(yummy, 0), 4);
+        assert_eq!(Instruction::nibble_right(yummy, 1), 3);
+        assert_eq!(Instruction::nibble_right(yummy, 2), 2);
+        assert_eq!(Instruction::nibble_right(yummy, 3), 1);
+    }
+
+    #[test]
+    fn stack_pushing_and_popping() {
+        let mut stack = Stack::new();
+        stack.push(10).unwrap();
+        stack.push(20).unwrap();
+        assert_eq!(stack.pop(), Some(20));
+        stack.push(30).unwrap();
+        assert_eq!(stack.pop(), Some(30));
+        assert_eq!(stack.pop(), Some(10));
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn decrement_timers() {
+        let mut timers = Timers::new();
+        timers.delay_timer = 90;
+        timers.sound_timer = 30;
+        thread::sleep(Duration::from_millis(500));
+        timers.decrement_timers();
+        assert!(approx_equal_u8(timers.delay_timer, 60, 1));
+        assert!(approx_equal_u8(timers.sound_timer, 0, 1));
+        thread::sleep(Duration::from_millis(500));
+        timers.decrement_timers();
+        timers.decrement_timers();
+        assert!(approx_equal_u8(timers.delay_timer, 30, 1));
+        assert!(approx_equal_u8(timers.sound_timer, 0, 1));
+    }
+
+    #[test]
+    fn timer_opcodes() {
+        let mut interpreter =
+            Interpreter::with_clock(InterpreterConfig::default(), Box::new(MockClock::new()));
+        // V0 = 200, delay = V0, sound = V0, V1 = delay
+        interpreter.load_program(&[0x60, 0xC8, 0xF0, 0x15, 0xF0, 0x18, 0xF1, 0x07]);
+
+        assert_eq!(interpreter.run_cycles(4), RunOutcome::Completed);
+        assert_eq!(interpreter.timers.delay_timer, 200);
+        assert_eq!(interpreter.timers.sound_timer, 200);
+        assert_eq!(interpreter.registers[1], 200);
+    }
+
+    #[test]
+    fn decrement_timers_near_max_doesnt_overflow() {
+        let clock = MockClock::new();
+        let mut timers = Timers::with_clock(Box::new(clock.clone()));
+        timers.delay_timer = 200;
+        timers.sound_timer = 200;
+
+        clock.advance(Duration::from_millis(100));
+        timers.decrement_timers();
+        assert_eq!(timers.delay_timer, 194);
+        assert_eq!(timers.sound_timer, 194);
+    }
+
+    #[test]
+    fn nanos_accumulator_has_no_drift() {
+        let clock = MockClock::new();
+        let mut timers = Timers::with_clock(Box::new(clock.clone()));
+        timers.accumulator = TimerAccumulator::Nanos;
+
+        let mut frames = 0;
+        for _ in 0..10 * 60 * 1000 {
+            clock.advance(Duration::from_millis(1));
+            frames += timers.decrement_timers();
+        }
+        assert_eq!(frames, 36000);
+        assert_eq!(timers.frames, 36000);
+    }
+
+    #[test]
+    fn timer_hz_slows_decrements() {
+        for accumulator in [TimerAccumulator::Float, TimerAccumulator::Nanos] {
+            let clock = MockClock::new();
+            let config = InterpreterConfig {
+                timer_hz: 30,
+                timer_accumulator: accumulator,
+                ..Default::default()
+            };
+            let mut interpreter = Interpreter::with_clock(config, Box::new(clock.clone()));
+            interpreter.load_program(&[0x12, 0x00]);
+            interpreter.timers.delay_timer = 100;
+
+            clock.advance(Duration::from_secs(1));
+            assert_eq!(interpreter.tick_frame(), RunOutcome::Completed);
+            assert_eq!(interpreter.timers.delay_timer, 70, "{accumulator:?}");
+            assert_eq!(interpreter.frames(), 30);
+        }
+
+        // Cycle-cost frames run twice the machine cycles for twice the emulated time.
+        let mut interpreter = Interpreter::with_clock(
+            InterpreterConfig {
+                timer_hz: 30,
+                timing: Timing::CycleCost,
+                ..Default::default()
+            },
+            Box::new(MockClock::new()),
+        );
+        interpreter.load_program(&[0x12, 0x00]);
+        assert_eq!(interpreter.tick_frame(), RunOutcome::Completed);
+        assert_eq!(
+            interpreter.cycles(),
+            (MACHINE_CYCLES_PER_SECOND as u64 / 30).div_ceil(12)
+        );
+        assert_eq!(interpreter.emulated_time(), interpreter.frame_duration());
+    }
+
+    #[test]
+    #[should_panic(expected = "the clock rate")]
+    fn clock_hz_is_validated() {
+        Interpreter::builder().clock_hz(0).build();
+    }
+
+    #[test]
+    #[should_panic(expected = "the timer rate")]
+    fn timer_hz_is_validated() {
+        Interpreter::with_config(InterpreterConfig {
+            timer_hz: 0,
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn scanline_callback() {
+        // V0 = 2, V1 = 3, I = glyph 0, draw at (2, 3)
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x60, 0x02, 0x61, 0x03, 0xA0, 0x50, 0xD0, 0x15]);
+        let scanlines = Arc::new(Mutex::new(Vec::new()));
+        let log = scanlines.clone();
+        interpreter.set_on_scanline(move |row, bits| log.lock().unwrap().push((row, bits)));
+
+        assert_eq!(interpreter.run_cycles(4), RunOutcome::Completed);
+        assert_eq!(
+            *scanlines.lock().unwrap(),
+            [
+                (3, 0b111100),
+                (4, 0b100100),
+                (5, 0b100100),
+                (6, 0b100100),
+                (7, 0b111100),
+            ]
+        );
+    }
+
+    #[test]
+    fn last_draw_bounds() {
+        // V0 = 10, V1 = 4, I = glyph 0, draw, V0 = 60, V1 = 30, draw
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[
+            0x60, 0x0A, 0x61, 0x04, 0xA0, 0x50, 0xD0, 0x15, 0x60, 0x3C, 0x61, 0x1E, 0xD0, 0x15,
+        ]);
+        assert_eq!(interpreter.last_draw_bounds(), None);
+
+        assert_eq!(interpreter.run_cycles(4), RunOutcome::Completed);
+        assert_eq!(interpreter.last_draw_bounds(), Some((10, 4, 8, 5)));
+        assert_eq!(interpreter.run_cycles(3), RunOutcome::Completed);
+        assert_eq!(interpreter.last_draw_bounds(), Some((60, 30, 4, 2)));
+    }
+
+    #[test]
+    fn timers_run_while_waiting_for_key() {
+        // V0 = 10, delay timer = V0, sound timer = V0, V1 = key
+        let clock = MockClock::new();
+        let mut interpreter =
+            Interpreter::with_clock(InterpreterConfig::default(), Box::new(clock.clone()));
+        interpreter.load_program(&[0x60, 0x0A, 0xF0, 0x15, 0xF0, 0x18, 0xF1, 0x0A]);
+
+        interpreter.tick_frame();
+        assert_eq!(interpreter.timers.delay_timer, 10);
+        for remaining in (6..10).rev() {
+            clock.advance(Duration::from_secs_f32(1.0 / 60.0));
+            assert_eq!(interpreter.tick_frame(), RunOutcome::Completed);
+            assert_eq!(interpreter.pc, 0x206);
+            assert_eq!(interpreter.timers.delay_timer, remaining);
+            assert_eq!(interpreter.timers.sound_timer, remaining);
+        }
+
+        interpreter.set_key(3, true);
+        clock.advance(Duration::from_secs_f32(1.0 / 60.0));
+        interpreter.tick_frame();
+        assert_eq!(interpreter.registers[1], 3);
+        assert_eq!(interpreter.timers.delay_timer, 5);
+    }
+
+    #[test]
+    fn last_collision_mask() {
+        // I = glyph 0, draw at 0, 0, V0 = 2, draw at 2, 0, V0 = 20, draw at 20, 0
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[
+            0xA0, 0x50, 0xD1, 0x15, 0x60, 0x02, 0xD0, 0x15, 0x60, 0x14, 0xD0, 0x15,
+        ]);
+        assert_eq!(interpreter.run_cycles(2), RunOutcome::Completed);
+        assert!(interpreter.last_collision_mask().iter().all(|hit| !hit));
+
+        // ####      #..#      #..#      #..#      ####
+        //   ####      #..#      #..#      #..#      ####
+        assert_eq!(interpreter.run_cycles(2), RunOutcome::Completed);
+        let mask = interpreter.last_collision_mask();
+        assert_eq!(mask.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+        let hits: Vec<_> = (0..mask.len())
+            .filter(|index| mask[*index])
+            .map(|index| (index % SCREEN_WIDTH, index / SCREEN_WIDTH))
+            .collect();
+        assert_eq!(hits, [(2, 0), (3, 0), (2, 4), (3, 4)]);
+
+        // A draw without collisions starts the mask over.
+        assert_eq!(interpreter.run_cycles(2), RunOutcome::Completed);
+        assert!(interpreter.last_collision_mask().iter().all(|hit| !hit));
+    }
+
+    #[test]
+    fn draw_start_position_wraps() {
+        let mut interpreter = Interpreter::new();
+        // V0 = 70, V1 = 34, I = glyph 0, draw
+        interpreter.load_program(&[0x60, 0x46, 0x61, 0x22, 0xA0, 0x50, 0xD0, 0x15]);
+        assert_eq!(interpreter.run_cycles(4), RunOutcome::Completed);
+
+        assert_screen(
+            &interpreter,
+            "
+            ..........
+            ..........
+            ......####
+            ......#..#
+            ......#..#
+            ......#..#
+            ......####
+            ",
+        );
+    }
+
+    #[test]
+    fn assert_screen_matches_drawn_glyph() {
+        let mut interpreter = Interpreter::new();
+        // V0 = 2, V1 = 1, I = glyph 7, draw at (2, 1)
+        interpreter.load_program(&[0x60, 0x02, 0x61, 0x01, 0xA0, 0x73, 0xD0, 0x15]);
+        assert_eq!(interpreter.run_cycles(4), RunOutcome::Completed);
+
+        assert_screen(
+            &interpreter,
+            "
+            ......
+            ..####
+            .....#
+            ....#.
+            ...#..
+            ...#..
+            ",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "row 1: expected ..#..., found ..####")]
+    fn assert_screen_reports_differences() {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x60, 0x02, 0x61, 0x01, 0xA0, 0x73, 0xD0, 0x15]);
+        assert_eq!(interpreter.run_cycles(4), RunOutcome::Completed);
+
+        assert_screen(&interpreter, "......\n..#...");
+    }
+
+    /// Compares the screen against ASCII art, `#` being lit and `.` being dark.
+    /// Leading whitespace and blank lines are ignored, and pixels outside of the art must be dark.
+    fn assert_screen(interpreter: &Interpreter, ascii: &str) {
+        let rows: Vec<&str> = ascii
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        let (width, height) = interpreter.display_size();
+        let render = |y: usize| -> String {
+            (0..width)
+                .map(|x| match interpreter.screen()[y * width + x] {
+                    0 => '.',
+                    _ => '#',
+                })
+                .collect()
+        };
+
+        let mut differences = Vec::new();
+        for y in 0..height {
+            let expected = rows.get(y).copied().unwrap_or("");
+            let actual = render(y);
+            let outside_lit = actual[expected.len()..].contains('#');
+            if actual[..expected.len()] != *expected || outside_lit {
+                differences.push(format!(
+                    "row {y}: expected {expected}, found {}{}",
+                    &actual[..expected.len()],
+                    if outside_lit {
+                        " (and more lit pixels)"
+                    } else {
+                        ""
+                    }
+                ));
+            }
+        }
+        assert!(
+            differences.is_empty(),
+            "screen doesn't match:\n{}",
+            differences.join("\n")
+        );
     }
 
     fn approx_equal_u8(lhs: u8, rhs: u8, max_deviation: u8) -> bool {
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+/// The COSMAC VIP hex keypad laid over the left side of a QWERTY keyboard, row by row.
+const DEFAULT_LAYOUT: [(char, u8); 16] = [
+    ('1', 0x1),
+    ('2', 0x2),
+    ('3', 0x3),
+    ('4', 0xC),
+    ('q', 0x4),
+    ('w', 0x5),
+    ('e', 0x6),
+    ('r', 0xD),
+    ('a', 0x7),
+    ('s', 0x8),
+    ('d', 0x9),
+    ('f', 0xE),
+    ('z', 0xA),
+    ('x', 0x0),
+    ('c', 0xB),
+    ('v', 0xF),
+];
+
+#[derive(Debug, PartialEq)]
+pub enum KeyMapError {
+    /// The line isn't of the form `host=hex` with a single host character and a hex digit.
+    InvalidLine(String),
+}
+
+/// Which host keyboard key presses which CHIP-8 key. The interpreter only knows about hex keys,
+/// front-ends translate their key events through this. Letters are matched case-insensitively.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyMap {
+    bindings: HashMap<char, u8>,
+}
+
+impl KeyMap {
+    /// A map without any bindings.
+    pub fn empty() -> Self {
+        Self {
+            bindings: HashMap::new(),
+        }
+    }
+
+    pub fn lookup(&self, host: char) -> Option<u8> {
+        self.bindings.get(&host.to_ascii_lowercase()).copied()
+    }
+
+    /// Binds `host` to `key`, replacing whatever host key pressed `key` before.
+    pub fn rebind(&mut self, host: char, key: u8) {
+        assert!(key < 16, "CHIP-8 keys go from 0x0 to 0xF");
+        self.bindings.retain(|_, bound| *bound != key);
+        self.bindings.insert(host.to_ascii_lowercase(), key);
+    }
+
+    pub fn unbind(&mut self, host: char) {
+        self.bindings.remove(&host.to_ascii_lowercase());
+    }
+
+    /// One `host=hex` line per binding, sorted by CHIP-8 key so the output is stable.
+    pub fn save(&self) -> String {
+        let mut bindings: Vec<_> = self.bindings.iter().collect();
+        bindings.sort_by_key(|(_, key)| **key);
+        bindings
+            .into_iter()
+            .map(|(host, key)| format!("{host}={key:X}\n"))
+            .collect()
+    }
+
+    /// Reads the format written by `save`. Blank lines are skipped.
+    pub fn load(text: &str) -> Result<Self, KeyMapError> {
+        let mut map = Self::empty();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let invalid = || KeyMapError::InvalidLine(line.to_string());
+            let (host, key) = line.split_once('=').ok_or_else(invalid)?;
+            let mut host = host.chars();
+            let (Some(host), None) = (host.next(), host.next()) else {
+                return Err(invalid());
+            };
+            let key = u8::from_str_radix(key, 16)
+                .ok()
+                .filter(|key| *key < 16)
+                .ok_or_else(invalid)?;
+            map.rebind(host, key);
+        }
+        Ok(map)
+    }
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self {
+            bindings: DEFAULT_LAYOUT.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_layout() {
+        let map = KeyMap::default();
+        assert_eq!(map.lookup('1'), Some(0x1));
+        assert_eq!(map.lookup('x'), Some(0x0));
+        assert_eq!(map.lookup('V'), Some(0xF));
+        assert_eq!(map.lookup('p'), None);
+    }
+
+    #[test]
+    fn rebind_key() {
+        let mut map = KeyMap::default();
+        map.rebind('k', 0x5);
+        assert_eq!(map.lookup('k'), Some(0x5));
+        assert_eq!(map.lookup('w'), None);
+
+        map.unbind('k');
+        assert_eq!(map.lookup('k'), None);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut map = KeyMap::default();
+        map.rebind('i', 0x8);
+        assert_eq!(KeyMap::load(&map.save()), Ok(map));
+
+        assert_eq!(
+            KeyMap::load("ab=1"),
+            Err(KeyMapError::InvalidLine("ab=1".to_string()))
+        );
+        assert_eq!(
+            KeyMap::load("a=10"),
+            Err(KeyMapError::InvalidLine("a=10".to_string()))
+        );
+    }
+}
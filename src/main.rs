@@ -1,5 +1,7 @@
 #![allow(dead_code)]
+mod audio;
 mod interpreter;
+mod keymap;
 
 fn main() {
     println!("Hello, world!");